@@ -16,15 +16,17 @@ pub fn check_session_exists() -> bool {
     session::Session::exists()
 }
 
-/// Persist the SEQTA `base_url` and `JSESSIONID`.
+/// Persist the SEQTA `base_url` and `JSESSIONID` as a profile.
 #[tauri::command]
 pub fn save_session(base_url: String, jsessionid: String) -> Result<(), String> {
-    session::Session {
+    session::Profiles::add_login(session::Session {
         base_url,
         jsessionid,
         additional_cookies: Vec::new(),
-    }
-    .save()
+        expires_at: None,
+        expired: false,
+    })
+    .map(|_| ())
     .map_err(|e| e.to_string())
 }
 
@@ -37,7 +39,7 @@ pub async fn logout() -> bool {
     }
 }
 
-/// Open a login window and harvest the cookie once the user signs in.
+/// Open a login window and save the cookie as a profile once the user signs in.
 #[tauri::command]
 pub async fn create_login_window(app: tauri::AppHandle, url: String) -> Result<(), String> {
     use tauri::{WebviewUrl, WebviewWindowBuilder};
@@ -153,9 +155,11 @@ pub async fn create_login_window(app: tauri::AppHandle, url: String) -> Result<(
                                                 base_url,
                                                 jsessionid: value,
                                                 additional_cookies,
+                                                expires_at: Some(expire_time.unix_timestamp()),
+                                                expired: false,
                                             };
 
-                                            if let Err(err) = session.save() {
+                                            if let Err(err) = session::Profiles::add_login(session) {
                                                 eprintln!("Failed to save session: {}", err);
                                             }
 
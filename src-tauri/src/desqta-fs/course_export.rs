@@ -0,0 +1,306 @@
+use chrono::Local;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter};
+use tokio_util::sync::CancellationToken;
+
+use crate::downloads;
+use crate::neterror::NetError;
+use crate::seqta::{self, Course, Subject};
+
+/// The running export, if any. Only one runs at a time.
+static JOB: Mutex<Option<CancellationToken>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFileStatus {
+    Downloaded,
+    /// Already present with the same size.
+    Skipped,
+    Failed,
+}
+
+/// One file in `manifest.json`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestEntry {
+    pub subject: String,
+    pub term: Option<i64>,
+    pub week: Option<i64>,
+    pub lesson: Option<String>,
+    pub file_name: String,
+    pub uuid: String,
+    /// Relative to the export folder.
+    pub path: String,
+    pub size: Option<u64>,
+    pub status: ExportFileStatus,
+    pub error: Option<String>,
+}
+
+/// Written to `manifest.json` in the export folder and sent with `course-export-finished`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Manifest {
+    pub exported_at: String,
+    pub directory: String,
+    pub cancelled: bool,
+    pub files: Vec<ManifestEntry>,
+}
+
+/// Payload of the `course-export-progress` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportProgress {
+    pub subject: String,
+    pub file_name: String,
+    /// Files handled so far, across all subjects.
+    pub done: usize,
+    /// Files found so far; grows as each subject's course is loaded.
+    pub total: usize,
+}
+
+/// A file to fetch and where it goes, relative to the export folder.
+struct PlannedFile {
+    subject: String,
+    term: Option<i64>,
+    week: Option<i64>,
+    lesson: Option<String>,
+    file_name: String,
+    uuid: String,
+    relative: PathBuf,
+    size: Option<u64>,
+}
+
+fn folder_name(name: &str, fallback: &str) -> String {
+    let name = downloads::sanitise_file_name(name);
+    if name.is_empty() { fallback.to_string() } else { name }
+}
+
+/// Make `relative` unique within this export, so two resources with the same name in one
+/// week do not overwrite each other. The course order is stable, so re-running an export
+/// gives every file the same path again.
+fn claim_path(used: &mut HashSet<PathBuf>, relative: PathBuf) -> PathBuf {
+    if used.insert(relative.clone()) {
+        return relative;
+    }
+    let name = relative.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let (stem, ext) = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem.to_string(), format!(".{}", ext)),
+        _ => (name.clone(), String::new()),
+    };
+    let claimed = (2..)
+        .map(|n| relative.with_file_name(format!("{} ({}){}", stem, n, ext)))
+        .find(|p| !used.contains(p))
+        .unwrap();
+    used.insert(claimed.clone());
+    claimed
+}
+
+/// Every file linked from a course: the course files, then each week's lesson resources
+/// under `Subject/Term N/Week M`.
+fn plan_course(subject_dir: &str, course: &Course, used: &mut HashSet<PathBuf>) -> Vec<PlannedFile> {
+    let mut planned = Vec::new();
+    let mut seen = HashSet::new();
+
+    for file in &course.files {
+        if !seen.insert(file.uuid.clone()) {
+            continue;
+        }
+        let file_name = folder_name(&file.filename, &file.uuid);
+        planned.push(PlannedFile {
+            subject: subject_dir.to_string(),
+            term: None,
+            week: None,
+            lesson: None,
+            relative: claim_path(used, Path::new(subject_dir).join("Course files").join(&file_name)),
+            file_name,
+            uuid: file.uuid.clone(),
+            size: file.size.parse().ok(),
+        });
+    }
+
+    for week in &course.weeks {
+        let Some(lessons) = course.lessons.get(week.index) else { continue };
+        let dir = Path::new(subject_dir)
+            .join(format!("Term {}", week.term))
+            .join(format!("Week {}", week.week));
+        for lesson in lessons {
+            for resource in &lesson.resources {
+                if !seen.insert(resource.uuid.clone()) {
+                    continue;
+                }
+                let file_name = folder_name(&resource.filename, &resource.uuid);
+                planned.push(PlannedFile {
+                    subject: subject_dir.to_string(),
+                    term: Some(week.term),
+                    week: Some(week.week),
+                    lesson: Some(lesson.title.clone()).filter(|t| !t.is_empty()),
+                    relative: claim_path(used, dir.join(&file_name)),
+                    file_name,
+                    uuid: resource.uuid.clone(),
+                    size: resource.size.parse().ok(),
+                });
+            }
+        }
+    }
+    planned
+}
+
+fn is_present(path: &Path, size: Option<u64>) -> bool {
+    match (std::fs::metadata(path), size) {
+        (Ok(meta), Some(size)) => meta.is_file() && meta.len() == size,
+        (Ok(meta), None) => meta.is_file(),
+        _ => false,
+    }
+}
+
+async fn run_export(
+    app: &AppHandle,
+    root: &Path,
+    subjects: Vec<(Subject, String)>,
+    cancel: &CancellationToken,
+) -> Manifest {
+    let mut manifest = Manifest {
+        exported_at: Local::now().to_rfc3339(),
+        directory: root.to_string_lossy().to_string(),
+        cancelled: false,
+        files: Vec::new(),
+    };
+    let mut used = HashSet::new();
+    let mut total = 0;
+
+    for (subject, subject_dir) in subjects {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let course = match seqta::get_course(subject.programme, subject.metaclass).await {
+            Ok(course) => course,
+            Err(e) => {
+                eprintln!("[Desqta] Failed to load course for {}: {}", subject.code, e);
+                continue;
+            }
+        };
+        let planned = plan_course(&subject_dir, &course, &mut used);
+        total += planned.len();
+
+        for file in planned {
+            if cancel.is_cancelled() {
+                break;
+            }
+            let _ = app.emit(
+                "course-export-progress",
+                ExportProgress {
+                    subject: file.subject.clone(),
+                    file_name: file.file_name.clone(),
+                    done: manifest.files.len(),
+                    total,
+                },
+            );
+
+            let path = root.join(&file.relative);
+            let (status, error) = if is_present(&path, file.size) {
+                (ExportFileStatus::Skipped, None)
+            } else {
+                match downloads::save_seqta_file("resource", &file.uuid, &path, cancel).await {
+                    Ok(true) => (ExportFileStatus::Downloaded, None),
+                    // Cancelled part-way; leave it out of the manifest
+                    Ok(false) => break,
+                    Err(e) => (ExportFileStatus::Failed, Some(e.to_string())),
+                }
+            };
+            manifest.files.push(ManifestEntry {
+                subject: file.subject,
+                term: file.term,
+                week: file.week,
+                lesson: file.lesson,
+                file_name: file.file_name,
+                uuid: file.uuid,
+                path: file.relative.to_string_lossy().to_string(),
+                size: std::fs::metadata(&path).ok().map(|m| m.len()).or(file.size),
+                status,
+                error,
+            });
+        }
+    }
+    manifest.cancelled = cancel.is_cancelled();
+    manifest
+}
+
+/// Download every resource linked from the courses of the current subjects (or all years,
+/// with `include_inactive`) into `directory`, as `Subject/Term N/Week M/<file>`. Files that
+/// are already there with the same size are skipped, so an export can be re-run to pick up
+/// new material. Runs in the background: progress is reported through
+/// `course-export-progress`, and the manifest, also written to `manifest.json`, through
+/// `course-export-finished`.
+#[tauri::command]
+pub async fn export_course_resources(
+    app: AppHandle,
+    directory: String,
+    include_inactive: Option<bool>,
+) -> Result<(), NetError> {
+    let cancel = {
+        let mut job = JOB.lock().unwrap();
+        if job.is_some() {
+            return Err(NetError::Rejected { message: "A course export is already running".to_string() });
+        }
+        let cancel = CancellationToken::new();
+        *job = Some(cancel.clone());
+        cancel
+    };
+
+    let folders = match seqta::get_subjects().await {
+        Ok(folders) => folders,
+        Err(e) => {
+            *JOB.lock().unwrap() = None;
+            return Err(e);
+        }
+    };
+    let include_inactive = include_inactive.unwrap_or(false);
+    let subjects: Vec<(Subject, String)> = folders
+        .into_iter()
+        .filter(|f| include_inactive || f.active)
+        .flat_map(|f| {
+            let year = (!f.active).then(|| f.code.clone());
+            f.subjects.into_iter().map(move |s| {
+                let name = folder_name(if s.title.is_empty() { &s.code } else { &s.title }, &s.code);
+                // Older years' subjects often share names with this year's
+                let dir = match &year {
+                    Some(year) => format!("{} ({})", name, year),
+                    None => name,
+                };
+                (s, dir)
+            })
+        })
+        .collect();
+
+    let root = PathBuf::from(directory);
+    tokio::fs::create_dir_all(&root).await?;
+    tauri::async_runtime::spawn(async move {
+        let manifest = run_export(&app, &root, subjects, &cancel).await;
+        if let Err(e) = std::fs::write(root.join("manifest.json"), serde_json::to_string_pretty(&manifest).unwrap()) {
+            eprintln!("[Desqta] Failed to write export manifest: {}", e);
+        }
+        println!(
+            "[Desqta] Course export {}: {} downloaded, {} skipped, {} failed",
+            if manifest.cancelled { "cancelled" } else { "finished" },
+            manifest.files.iter().filter(|f| f.status == ExportFileStatus::Downloaded).count(),
+            manifest.files.iter().filter(|f| f.status == ExportFileStatus::Skipped).count(),
+            manifest.files.iter().filter(|f| f.status == ExportFileStatus::Failed).count(),
+        );
+        *JOB.lock().unwrap() = None;
+        let _ = app.emit("course-export-finished", &manifest);
+    });
+    Ok(())
+}
+
+/// Stop the running course export. The file being downloaded is abandoned and fetched
+/// again by the next export.
+#[tauri::command]
+pub fn cancel_course_export() -> bool {
+    match JOB.lock().unwrap().as_ref() {
+        Some(cancel) => {
+            cancel.cancel();
+            true
+        }
+        None => false,
+    }
+}
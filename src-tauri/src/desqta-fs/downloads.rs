@@ -0,0 +1,518 @@
+use chrono::Local;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+use tokio_util::sync::CancellationToken;
+
+use crate::neterror::NetError;
+use crate::netgrab;
+use crate::session;
+
+const FILE_ENDPOINT: &str = "/seqta/student/load/file";
+/// Finished downloads remembered in the history.
+const MAX_HISTORY: usize = 200;
+/// Minimum time between `download-progress` events for one download.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Cancellation tokens of the downloads currently running, by id.
+static ACTIVE: OnceLock<Mutex<HashMap<String, CancellationToken>>> = OnceLock::new();
+/// Serialises read-modify-write of `downloads.json`.
+static HISTORY_LOCK: Mutex<()> = Mutex::new(());
+
+fn active() -> &'static Mutex<HashMap<String, CancellationToken>> {
+    ACTIVE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadStatus {
+    Downloading,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// A file fetched from SEQTA, as kept in the download history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Download {
+    pub id: String,
+    /// SEQTA file type, e.g. `resource`, `submission` or `report`.
+    pub file_type: String,
+    pub uuid: String,
+    pub file_name: String,
+    /// Final location; data is written to `<path>.part` until the download completes.
+    pub path: String,
+    pub downloaded: u64,
+    pub total: Option<u64>,
+    pub status: DownloadStatus,
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Unix timestamps (seconds).
+    pub started_at: i64,
+    #[serde(default)]
+    pub finished_at: Option<i64>,
+}
+
+/// Payload of the `download-progress` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadProgress {
+    pub id: String,
+    pub downloaded: u64,
+    pub total: Option<u64>,
+    /// Bytes per second since the previous event.
+    pub rate: u64,
+}
+
+/// Location: `$DATA_DIR/DesQTA/profiles/<id>/downloads.json`
+fn history_file() -> PathBuf {
+    let mut dir = session::profile_dir();
+    dir.push("downloads.json");
+    dir
+}
+
+/// The history as saved. A download still marked as running that is not running any more
+/// was cut off by the app closing, so it is reported as failed and can be resumed.
+fn load_history() -> Vec<Download> {
+    let mut history: Vec<Download> = std::fs::read_to_string(history_file())
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default();
+    let running = active().lock().unwrap();
+    for d in history
+        .iter_mut()
+        .filter(|d| d.status == DownloadStatus::Downloading && !running.contains_key(&d.id))
+    {
+        d.status = DownloadStatus::Failed;
+        d.error = Some("Interrupted".to_string());
+    }
+    history
+}
+
+/// Insert or replace a download in the history, newest first.
+fn record(download: &Download) {
+    let _guard = HISTORY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut history = load_history();
+    history.retain(|d| d.id != download.id);
+    history.insert(0, download.clone());
+    history.truncate(MAX_HISTORY);
+    if let Err(e) = std::fs::write(history_file(), serde_json::to_string(&history).unwrap()) {
+        eprintln!("[Desqta] Failed to save download history: {}", e);
+    }
+}
+
+fn now_secs() -> i64 {
+    Local::now().timestamp()
+}
+
+fn part_path(path: &Path) -> PathBuf {
+    let mut part = path.as_os_str().to_owned();
+    part.push(".part");
+    PathBuf::from(part)
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// File name from a `Content-Disposition` header, preferring the RFC 5987 `filename*` form.
+fn disposition_file_name(resp: &reqwest::Response) -> Option<String> {
+    let header = resp.headers().get(reqwest::header::CONTENT_DISPOSITION)?.to_str().ok()?;
+    parse_disposition(header)
+}
+
+fn parse_disposition(header: &str) -> Option<String> {
+    let params: Vec<(String, String)> = header
+        .split(';')
+        .filter_map(|p| {
+            let (k, v) = p.split_once('=')?;
+            Some((k.trim().to_lowercase(), v.trim().trim_matches('"').to_string()))
+        })
+        .collect();
+    params
+        .iter()
+        .find(|(k, _)| k == "filename*")
+        .map(|(_, v)| percent_decode(v.rsplit("''").next().unwrap_or(v)))
+        .or_else(|| params.iter().find(|(k, _)| k == "filename").map(|(_, v)| v.clone()))
+}
+
+/// Strip characters that are not allowed in file names on any desktop platform.
+pub(crate) fn sanitise_file_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| if c.is_control() || "/\\:*?\"<>|".contains(c) { '_' } else { c })
+        .collect();
+    cleaned.trim().trim_matches('.').to_string()
+}
+
+/// `dir/name`, or `dir/name (n).ext` if that is taken.
+fn unique_path(dir: &Path, name: &str) -> PathBuf {
+    let candidate = dir.join(name);
+    let taken = |p: &Path| p.exists() || part_path(p).exists();
+    if !taken(&candidate) {
+        return candidate;
+    }
+    let (stem, ext) = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, format!(".{}", ext)),
+        _ => (name, String::new()),
+    };
+    (1..)
+        .map(|n| dir.join(format!("{} ({}){}", stem, n, ext)))
+        .find(|p| !taken(p))
+        .unwrap()
+}
+
+fn default_download_dir() -> PathBuf {
+    let mut dir = dirs_next::download_dir().unwrap_or_else(|| dirs_next::home_dir().unwrap_or_default());
+    dir.push("DesQTA");
+    dir
+}
+
+/// Request a SEQTA file, starting at `offset` bytes when resuming.
+async fn open_stream(file_type: &str, uuid: &str, offset: u64) -> Result<reqwest::Response, NetError> {
+    let (profile_id, session) = session::Session::load_active();
+    let url = format!("{}{}", session.base_url, FILE_ENDPOINT);
+    let mut request = netgrab::create_client()
+        .get(&url)
+        .query(&[("type", file_type), ("file", uuid)]);
+    request = netgrab::append_default_headers(request).await;
+    if offset > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", offset));
+    }
+
+    let resp = request.send().await?;
+    let status = resp.status();
+    let final_path = resp.url().path().to_lowercase();
+    if status == reqwest::StatusCode::UNAUTHORIZED
+        || status == reqwest::StatusCode::FORBIDDEN
+        || final_path.contains("login")
+        || final_path.contains("saml")
+    {
+        return Err(netgrab::session_expired(profile_id.as_deref()));
+    }
+    // Resuming past the end; the caller decides whether the file was already complete
+    if status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE && offset > 0 {
+        return Ok(resp);
+    }
+    if !status.is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        return Err(NetError::http(status.as_u16(), body));
+    }
+    Ok(resp)
+}
+
+/// Full size of the file from a 416 response's `Content-Range: bytes */<total>`.
+fn unsatisfied_range_total(resp: &reqwest::Response) -> Option<u64> {
+    resp.headers()
+        .get(reqwest::header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .strip_prefix("bytes */")?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Total size of the file, taking a partial `Content-Range` response into account.
+fn total_size(resp: &reqwest::Response, offset: u64) -> Option<u64> {
+    if resp.status() == reqwest::StatusCode::PARTIAL_CONTENT {
+        resp.headers()
+            .get(reqwest::header::CONTENT_RANGE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.rsplit('/').next())
+            .and_then(|v| v.parse().ok())
+            .or_else(|| resp.content_length().map(|len| len + offset))
+    } else {
+        resp.content_length()
+    }
+}
+
+/// Stream the response body into `<path>.part`, moving it into place once complete.
+/// `on_chunk` is called with the size of each chunk written.
+/// Returns `Ok(false)` if the download was cancelled.
+async fn write_body(
+    mut resp: reqwest::Response,
+    path: &Path,
+    cancel: &CancellationToken,
+    mut on_chunk: impl FnMut(u64),
+) -> Result<bool, NetError> {
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    let part = part_path(path);
+
+    // Without a partial response the server ignored the Range header; start over
+    let resumed = resp.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(&part)
+        .await?;
+
+    loop {
+        let chunk = tokio::select! {
+            _ = cancel.cancelled() => {
+                file.flush().await?;
+                return Ok(false);
+            }
+            chunk = resp.chunk() => chunk?,
+        };
+        let Some(chunk) = chunk else { break };
+        file.write_all(&chunk).await?;
+        on_chunk(chunk.len() as u64);
+    }
+    file.flush().await?;
+    drop(file);
+
+    tokio::fs::rename(&part, path).await?;
+    Ok(true)
+}
+
+/// Write a tracked download, emitting throttled `download-progress` events.
+async fn write_download(
+    app: &AppHandle,
+    download: &mut Download,
+    resp: reqwest::Response,
+    cancel: &CancellationToken,
+) -> Result<bool, NetError> {
+    if resp.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+        download.downloaded = 0;
+    }
+    let path = PathBuf::from(&download.path);
+    let mut last_emit = Instant::now();
+    let mut bytes_since_emit = 0u64;
+    write_body(resp, &path, cancel, |len| {
+        download.downloaded += len;
+        bytes_since_emit += len;
+        let elapsed = last_emit.elapsed();
+        if elapsed >= PROGRESS_INTERVAL {
+            let _ = app.emit(
+                "download-progress",
+                DownloadProgress {
+                    id: download.id.clone(),
+                    downloaded: download.downloaded,
+                    total: download.total,
+                    rate: (bytes_since_emit as f64 / elapsed.as_secs_f64()) as u64,
+                },
+            );
+            last_emit = Instant::now();
+            bytes_since_emit = 0;
+        }
+    })
+    .await
+}
+
+/// Download a SEQTA file straight to `path` without recording it in the history, for
+/// bulk jobs that report their own progress. Returns `Ok(false)` if cancelled.
+pub(crate) async fn save_seqta_file(
+    file_type: &str,
+    uuid: &str,
+    path: &Path,
+    cancel: &CancellationToken,
+) -> Result<bool, NetError> {
+    let resp = open_stream(file_type, uuid, 0).await?;
+    write_body(resp, path, cancel, |_| {}).await
+}
+
+/// Run a download to completion in the background, recording the outcome in the history.
+fn spawn_transfer(app: AppHandle, mut download: Download, resp: reqwest::Response) {
+    let cancel = CancellationToken::new();
+    active().lock().unwrap().insert(download.id.clone(), cancel.clone());
+    download.status = DownloadStatus::Downloading;
+    download.error = None;
+    download.finished_at = None;
+    record(&download);
+
+    tauri::async_runtime::spawn(async move {
+        match write_download(&app, &mut download, resp, &cancel).await {
+            Ok(true) => {
+                download.status = DownloadStatus::Completed;
+                download.total = Some(download.downloaded);
+                println!("[Desqta] Downloaded {} to {}", download.file_name, download.path);
+            }
+            Ok(false) => download.status = DownloadStatus::Cancelled,
+            Err(e) => {
+                eprintln!("[Desqta] Download of {} failed: {}", download.file_name, e);
+                download.status = DownloadStatus::Failed;
+                download.error = Some(e.to_string());
+            }
+        }
+        download.finished_at = Some(now_secs());
+        active().lock().unwrap().remove(&download.id);
+        record(&download);
+        let _ = app.emit("download-finished", &download);
+    });
+}
+
+/// Start downloading a SEQTA file to `directory` (default: `Downloads/DesQTA`).
+/// Returns as soon as the transfer has started; progress is reported through
+/// `download-progress` events and the outcome through `download-finished`.
+#[tauri::command]
+pub async fn download_seqta_file(
+    app: AppHandle,
+    file_type: String,
+    uuid: String,
+    file_name: Option<String>,
+    directory: Option<String>,
+) -> Result<Download, NetError> {
+    let resp = open_stream(&file_type, &uuid, 0).await?;
+
+    let name = file_name
+        .or_else(|| disposition_file_name(&resp))
+        .map(|n| sanitise_file_name(&n))
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| uuid.clone());
+    let dir = directory.map(PathBuf::from).unwrap_or_else(default_download_dir);
+    tokio::fs::create_dir_all(&dir).await?;
+    let path = unique_path(&dir, &name);
+
+    let mut id_bytes = [0u8; 8];
+    rand::rngs::OsRng.fill_bytes(&mut id_bytes);
+    let download = Download {
+        id: id_bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+        file_type,
+        uuid,
+        file_name: name,
+        path: path.to_string_lossy().to_string(),
+        downloaded: 0,
+        total: resp.content_length(),
+        status: DownloadStatus::Downloading,
+        error: None,
+        started_at: now_secs(),
+        finished_at: None,
+    };
+    spawn_transfer(app, download.clone(), resp);
+    Ok(download)
+}
+
+/// Continue a failed or cancelled download from where it stopped.
+#[tauri::command]
+pub async fn resume_download(app: AppHandle, id: String) -> Result<Download, NetError> {
+    let mut download = load_history()
+        .into_iter()
+        .find(|d| d.id == id)
+        .ok_or_else(|| NetError::NotFound { message: format!("No download with id {}", id) })?;
+    if !matches!(download.status, DownloadStatus::Failed | DownloadStatus::Cancelled) {
+        return Ok(download);
+    }
+
+    let offset = std::fs::metadata(part_path(Path::new(&download.path)))
+        .map(|m| m.len())
+        .unwrap_or(0);
+    let resp = open_stream(&download.file_type, &download.uuid, offset).await?;
+    if resp.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        // The whole file was written before the download stopped; only the rename is left
+        if unsatisfied_range_total(&resp) != Some(offset) {
+            let body = resp.text().await.unwrap_or_default();
+            return Err(NetError::http(416, body));
+        }
+        tokio::fs::rename(part_path(Path::new(&download.path)), &download.path).await?;
+        download.downloaded = offset;
+        download.total = Some(offset);
+        download.status = DownloadStatus::Completed;
+        download.error = None;
+        download.finished_at = Some(now_secs());
+        record(&download);
+        let _ = app.emit("download-finished", &download);
+        return Ok(download);
+    }
+    download.downloaded = offset;
+    download.total = total_size(&resp, offset);
+    spawn_transfer(app, download.clone(), resp);
+    Ok(download)
+}
+
+/// Stop a running download. The partial file is kept so it can be resumed.
+#[tauri::command]
+pub fn cancel_download(id: String) -> bool {
+    match active().lock().unwrap().get(&id) {
+        Some(token) => {
+            token.cancel();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Download history, newest first.
+#[tauri::command]
+pub fn list_downloads() -> Vec<Download> {
+    load_history()
+}
+
+/// Forget finished downloads, deleting the partial files of any that did not complete.
+#[tauri::command]
+pub fn clear_download_history() -> Result<(), String> {
+    let _guard = HISTORY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let history = load_history();
+    let running = active().lock().unwrap();
+    let (keep, forget): (Vec<Download>, Vec<Download>) =
+        history.into_iter().partition(|d| running.contains_key(&d.id));
+    for d in forget.iter().filter(|d| d.status != DownloadStatus::Completed) {
+        let _ = std::fs::remove_file(part_path(Path::new(&d.path)));
+    }
+    std::fs::write(history_file(), serde_json::to_string(&keep).unwrap()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_percent_escapes() {
+        assert_eq!(percent_decode("Year%2010%20Report.pdf"), "Year 10 Report.pdf");
+        assert_eq!(percent_decode("caf%C3%A9.docx"), "café.docx");
+        // Malformed escapes are kept as they are
+        assert_eq!(percent_decode("100%.txt"), "100%.txt");
+        assert_eq!(percent_decode("50%zz"), "50%zz");
+    }
+
+    #[test]
+    fn file_name_from_content_disposition() {
+        assert_eq!(parse_disposition(r#"attachment; filename="Essay.docx""#).as_deref(), Some("Essay.docx"));
+        assert_eq!(
+            parse_disposition(r#"attachment; filename="Essay.docx"; filename*=UTF-8''Caf%C3%A9%20Essay.docx"#)
+                .as_deref(),
+            Some("Café Essay.docx")
+        );
+        assert_eq!(parse_disposition("inline"), None);
+    }
+
+    #[test]
+    fn unique_path_numbers_taken_names() {
+        let dir = std::env::temp_dir().join(format!("desqta-unique-path-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(unique_path(&dir, "notes.pdf"), dir.join("notes.pdf"));
+
+        std::fs::write(dir.join("notes.pdf"), b"").unwrap();
+        std::fs::write(dir.join("notes (1).pdf.part"), b"").unwrap();
+        assert_eq!(unique_path(&dir, "notes.pdf"), dir.join("notes (2).pdf"));
+
+        std::fs::write(dir.join(".bashrc"), b"").unwrap();
+        assert_eq!(unique_path(&dir, ".bashrc"), dir.join(".bashrc (1)"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
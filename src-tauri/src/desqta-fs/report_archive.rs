@@ -0,0 +1,113 @@
+use serde::Serialize;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+use tauri_plugin_opener::OpenerExt;
+use tokio_util::sync::CancellationToken;
+
+use crate::downloads;
+use crate::neterror::NetError;
+use crate::seqta::{self, Report};
+use crate::session;
+
+/// A report along with where its PDF is archived, if it has been downloaded.
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchivedReport {
+    #[serde(flatten)]
+    pub report: Report,
+    pub path: Option<String>,
+}
+
+/// Location: `$DATA_DIR/DesQTA/profiles/<id>/reports/`
+fn archive_dir() -> PathBuf {
+    let mut dir = session::profile_dir();
+    dir.push("reports");
+    dir
+}
+
+/// `<year> <terms> <type> (<uuid prefix>).pdf`; the UUID keeps reports with the same
+/// title from overwriting each other.
+fn report_path(report: &Report) -> PathBuf {
+    let title = downloads::sanitise_file_name(&format!("{} {} {}", report.year, report.terms, report.types));
+    let short_id: String = report.uuid.chars().take(8).collect();
+    archive_dir().join(format!("{} ({}).pdf", title.trim(), short_id))
+}
+
+/// Whether `path` starts with the PDF header. SEQTA can answer with an error page instead
+/// of the report, which must not be archived as if it were one.
+fn is_pdf(path: &Path) -> bool {
+    let mut magic = [0u8; 5];
+    std::fs::File::open(path).and_then(|mut f| f.read_exact(&mut magic)).is_ok() && &magic == b"%PDF-"
+}
+
+fn archived(report: Report) -> ArchivedReport {
+    let path = report_path(&report);
+    ArchivedReport {
+        path: is_pdf(&path).then(|| path.to_string_lossy().to_string()),
+        report,
+    }
+}
+
+/// Whether the report's PDF is already in the archive.
+pub(crate) fn is_archived(report: &Report) -> bool {
+    is_pdf(&report_path(report))
+}
+
+/// Download a report's PDF into the archive unless it is already there.
+pub(crate) async fn archive_report(report: &Report) -> Result<PathBuf, NetError> {
+    let path = report_path(report);
+    if !is_pdf(&path) {
+        downloads::save_seqta_file("report", &report.uuid, &path, &CancellationToken::new()).await?;
+        if !is_pdf(&path) {
+            let _ = std::fs::remove_file(&path);
+            return Err(NetError::decode(format!("Report {} is not a PDF", report.uuid)));
+        }
+        println!("[Desqta] Archived report {}", path.display());
+    }
+    Ok(path)
+}
+
+async fn find_report(uuid: &str) -> Result<Report, NetError> {
+    seqta::reports(false)
+        .await?
+        .into_iter()
+        .find(|r| r.uuid == uuid)
+        .ok_or_else(|| NetError::NotFound { message: format!("No report with id {}", uuid) })
+}
+
+/// Reports published to the student, newest first, with their archive status.
+#[tauri::command]
+pub async fn list_reports() -> Result<Vec<ArchivedReport>, NetError> {
+    let mut reports = seqta::reports(false).await?;
+    reports.sort_by(|a, b| b.created_date.cmp(&a.created_date));
+    Ok(reports.into_iter().map(archived).collect())
+}
+
+/// Download a report to the archive, returning its path.
+#[tauri::command]
+pub async fn download_report(uuid: String) -> Result<String, NetError> {
+    let report = find_report(&uuid).await?;
+    archive_report(&report).await.map(|p| p.to_string_lossy().to_string())
+}
+
+/// Open a report in the default PDF viewer, archiving it first if needed.
+/// Returns the archived path.
+#[tauri::command]
+pub async fn open_report(app: AppHandle, uuid: String) -> Result<String, NetError> {
+    let report = find_report(&uuid).await?;
+    let path = archive_report(&report).await?.to_string_lossy().to_string();
+    app.opener()
+        .open_path(path.clone(), None::<&str>)
+        .map_err(|e| NetError::Io { message: e.to_string() })?;
+    Ok(path)
+}
+
+/// Open the report archive folder in the file manager.
+#[tauri::command]
+pub fn open_report_archive(app: AppHandle) -> Result<(), NetError> {
+    let dir = archive_dir();
+    std::fs::create_dir_all(&dir)?;
+    app.opener()
+        .open_path(dir.to_string_lossy(), None::<&str>)
+        .map_err(|e| NetError::Io { message: e.to_string() })
+}
@@ -0,0 +1,359 @@
+use futures_util::StreamExt;
+use rand::RngCore;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+use tokio_util::io::ReaderStream;
+use tokio_util::sync::CancellationToken;
+use url::form_urlencoded;
+
+use crate::neterror::NetError;
+use crate::netgrab;
+use crate::seqta::{self, SubmissionFile, UploadedFile};
+use crate::session;
+
+const UPLOAD_ENDPOINT: &str = "/seqta/student/file/upload/xhr2";
+/// Files larger than this are refused unless the caller passes a higher limit.
+const MAX_UPLOAD_BYTES: u64 = 1024 * 1024 * 1024;
+const CHUNK_SIZE: usize = 256 * 1024;
+/// Minimum time between `upload-progress` events for one upload.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+/// Uploads send far more than a normal request, so allow much longer than the client default.
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// Cancellation tokens of the uploads currently running, by id.
+static ACTIVE: OnceLock<Mutex<HashMap<String, CancellationToken>>> = OnceLock::new();
+
+fn active() -> &'static Mutex<HashMap<String, CancellationToken>> {
+    ACTIVE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Payload of the `upload-progress` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadProgress {
+    pub id: String,
+    pub uploaded: u64,
+    pub total: u64,
+    /// Bytes per second since the previous event.
+    pub rate: u64,
+}
+
+/// Whether `mime` (or the file's extension) matches one of SEQTA's accepted types, which
+/// may be exact types, wildcards like `image/*`, or extensions like `.pdf`.
+fn is_accepted(accept: &[String], mime: &str, file_name: &str) -> bool {
+    let file_name = file_name.to_lowercase();
+    accept.iter().map(|a| a.trim().to_lowercase()).any(|a| {
+        a == "*/*"
+            || a == mime
+            || a.strip_suffix("/*").is_some_and(|prefix| mime.starts_with(&format!("{}/", prefix)))
+            || (a.starts_with('.') && file_name.ends_with(&a))
+    })
+}
+
+/// Refuse files that SEQTA would reject anyway before spending time sending them.
+fn check_file(
+    path: &Path,
+    file_name: &str,
+    accept_mimes: Option<&[String]>,
+    max_bytes: u64,
+) -> Result<(u64, String), NetError> {
+    let size = std::fs::metadata(path)?.len();
+    if size == 0 {
+        return Err(NetError::Rejected { message: format!("{} is empty", file_name) });
+    }
+    if size > max_bytes {
+        return Err(NetError::Rejected {
+            message: format!("{} is {} MB, larger than the {} MB limit", file_name, size / 1_048_576, max_bytes / 1_048_576),
+        });
+    }
+
+    let mime = mime_guess::from_path(file_name).first_or_octet_stream().essence_str().to_string();
+    if let Some(accept) = accept_mimes.filter(|a| !a.is_empty()) {
+        if !is_accepted(accept, &mime, file_name) {
+            return Err(NetError::Rejected {
+                message: format!("{} files cannot be uploaded here (accepted: {})", mime, accept.join(", ")),
+            });
+        }
+    }
+    Ok((size, mime))
+}
+
+async fn send_file(
+    app: AppHandle,
+    id: String,
+    file_name: &str,
+    path: &Path,
+    accept_mimes: Option<Vec<String>>,
+    max_bytes: u64,
+    cancel: &CancellationToken,
+) -> Result<UploadedFile, NetError> {
+    let (total, mime) = check_file(path, file_name, accept_mimes.as_deref(), max_bytes)?;
+
+    let file = tokio::fs::File::open(path).await?;
+    let mut uploaded = 0u64;
+    let mut last_emit = Instant::now();
+    let mut bytes_since_emit = 0u64;
+    let progress_id = id.clone();
+    let stream = ReaderStream::with_capacity(file, CHUNK_SIZE).inspect(move |chunk| {
+        let Ok(chunk) = chunk else { return };
+        uploaded += chunk.len() as u64;
+        bytes_since_emit += chunk.len() as u64;
+        let elapsed = last_emit.elapsed();
+        if elapsed >= PROGRESS_INTERVAL || uploaded == total {
+            let _ = app.emit(
+                "upload-progress",
+                UploadProgress {
+                    id: progress_id.clone(),
+                    uploaded,
+                    total,
+                    rate: (bytes_since_emit as f64 / elapsed.as_secs_f64().max(0.001)) as u64,
+                },
+            );
+            last_emit = Instant::now();
+            bytes_since_emit = 0;
+        }
+    });
+
+    let (profile_id, session) = session::Session::load_active();
+    let url = format!("{}{}", session.base_url, UPLOAD_ENDPOINT);
+    let url_filename: String = form_urlencoded::byte_serialize(file_name.as_bytes()).collect();
+    let accept_header = accept_mimes
+        .filter(|a| !a.is_empty())
+        .map(|a| a.join(","))
+        .unwrap_or_else(|| "null".to_string());
+
+    // Set headers exactly like the web UI
+    let mut request = netgrab::create_client()
+        .post(&url)
+        .timeout(UPLOAD_TIMEOUT)
+        .header("X-File-Name", url_filename)
+        .header("X-Accept-Mimes", accept_header)
+        .header("X-Requested-With", "XMLHttpRequest")
+        .header(reqwest::header::CONTENT_TYPE, mime)
+        .header(reqwest::header::CONTENT_LENGTH, total)
+        .body(reqwest::Body::wrap_stream(stream));
+    request = netgrab::append_default_headers(request).await;
+
+    // Dropping the request future aborts the connection mid-body
+    let resp = tokio::select! {
+        _ = cancel.cancelled() => return Err(NetError::Cancelled),
+        resp = request.send() => resp?,
+    };
+    let status = resp.status();
+    if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+        return Err(netgrab::session_expired(profile_id.as_deref()));
+    }
+    let login_page = status.is_success() && netgrab::is_login_redirect(&url, &resp);
+    let text = resp.text().await?;
+    if !status.is_success() {
+        return Err(NetError::http(status.as_u16(), text));
+    }
+    if login_page {
+        return Err(netgrab::session_expired(profile_id.as_deref()));
+    }
+    seqta::decode_payload(UPLOAD_ENDPOINT, &text)
+}
+
+/// Upload one file, registering `id` so `cancel_upload` can stop it.
+async fn upload(
+    app: AppHandle,
+    id: String,
+    file_name: &str,
+    path: &Path,
+    accept_mimes: Option<Vec<String>>,
+    max_bytes: u64,
+) -> Result<UploadedFile, NetError> {
+    let cancel = CancellationToken::new();
+    active().lock().unwrap().insert(id.clone(), cancel.clone());
+    let result = send_file(app, id.clone(), file_name, path, accept_mimes, max_bytes, &cancel).await;
+    active().lock().unwrap().remove(&id);
+
+    match &result {
+        Ok(file) => println!("[Desqta] Uploaded {} as file {}", file_name, file.id),
+        Err(NetError::Cancelled) => println!("[Desqta] Upload of {} cancelled", file_name),
+        Err(e) => eprintln!("[Desqta] Upload of {} failed: {}", file_name, e),
+    }
+    result
+}
+
+/// Stream a file to SEQTA, reporting `upload-progress` events under `upload_id` so the
+/// frontend can show progress and cancel with `cancel_upload` while this is pending.
+/// `accept_mimes` mirrors the `X-Accept-Mimes` header the web UI sends for the drop zone.
+#[tauri::command]
+pub async fn upload_seqta_file(
+    app: AppHandle,
+    file_name: String,
+    file_path: String,
+    upload_id: Option<String>,
+    accept_mimes: Option<Vec<String>>,
+    max_bytes: Option<u64>,
+) -> Result<UploadedFile, NetError> {
+    let id = upload_id.unwrap_or_else(|| file_path.clone());
+    upload(app, id, &file_name, Path::new(&file_path), accept_mimes, max_bytes.unwrap_or(MAX_UPLOAD_BYTES)).await
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SubmissionFileStatus {
+    /// Uploaded and found in the submission afterwards.
+    Submitted,
+    /// Uploaded and linked, but missing when the submission was read back, or the read-back
+    /// failed. Do not resubmit; check the assessment in SEQTA.
+    NotVerified,
+    /// Uploaded, but not linked because another file failed.
+    Uploaded,
+    Failed,
+    Cancelled,
+}
+
+/// Outcome for one file passed to `submit_assessment`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SubmissionFileResult {
+    pub path: String,
+    /// Id used for `upload-progress` events and `cancel_upload`.
+    pub upload_id: String,
+    pub status: SubmissionFileStatus,
+    pub file: Option<UploadedFile>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SubmissionResult {
+    /// True only if every file was uploaded, linked and seen in the submission.
+    pub success: bool,
+    pub files: Vec<SubmissionFileResult>,
+    /// The submission as SEQTA reports it after saving.
+    pub submissions: Vec<SubmissionFile>,
+}
+
+/// Upload `files` and attach them to the submission for an assessment in one step.
+/// Files are only linked once all of them uploaded, so a failure never leaves a partial
+/// submission behind. The submission is read back afterwards to confirm each file landed.
+/// Upload ids are `<assessment_id>:<submit_id>:<index>`, so two submissions to the same
+/// assessment never share ids. `submit_id` is random if the caller does not pick one.
+#[tauri::command]
+pub async fn submit_assessment(
+    app: AppHandle,
+    assessment_id: i64,
+    metaclass_id: i64,
+    files: Vec<String>,
+    submit_id: Option<String>,
+) -> Result<SubmissionResult, NetError> {
+    let submit_id = submit_id.unwrap_or_else(|| {
+        let mut bytes = [0u8; 8];
+        rand::rngs::OsRng.fill_bytes(&mut bytes);
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    });
+    let mut results = Vec::with_capacity(files.len());
+    for (index, path) in files.iter().enumerate() {
+        let upload_id = format!("{}:{}:{}", assessment_id, submit_id, index);
+        let file_name = Path::new(path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.clone());
+        let result = upload(app.clone(), upload_id.clone(), &file_name, Path::new(path), None, MAX_UPLOAD_BYTES).await;
+        let cancelled = matches!(result, Err(NetError::Cancelled));
+        results.push(match result {
+            Ok(file) => SubmissionFileResult {
+                path: path.clone(),
+                upload_id,
+                status: SubmissionFileStatus::Uploaded,
+                file: Some(file),
+                error: None,
+            },
+            Err(e) => SubmissionFileResult {
+                path: path.clone(),
+                upload_id,
+                status: if cancelled { SubmissionFileStatus::Cancelled } else { SubmissionFileStatus::Failed },
+                file: None,
+                error: Some(e.to_string()),
+            },
+        });
+        // Stop at the first failure; nothing will be linked anyway
+        if results.last().is_some_and(|r| r.file.is_none()) {
+            break;
+        }
+    }
+
+    let all_uploaded = results.len() == files.len() && results.iter().all(|r| r.file.is_some());
+    if !all_uploaded {
+        return Ok(SubmissionResult { success: false, files: results, submissions: Vec::new() });
+    }
+
+    let ids: Vec<i64> = results.iter().filter_map(|r| r.file.as_ref().map(|f| f.id)).collect();
+    if let Err(e) = seqta::link_submission_files(assessment_id, metaclass_id, &ids).await {
+        for r in results.iter_mut() {
+            r.error = Some(format!("Uploaded but could not be attached: {}", e));
+        }
+        return Ok(SubmissionResult { success: false, files: results, submissions: Vec::new() });
+    }
+
+    // The files are attached by now, so a failed read-back must not look like a failed submit
+    let submissions = match seqta::submissions(assessment_id, metaclass_id, true).await {
+        Ok(submissions) => submissions,
+        Err(e) => {
+            eprintln!("[Desqta] Could not verify submission for assessment {}: {}", assessment_id, e);
+            for r in results.iter_mut() {
+                r.status = SubmissionFileStatus::NotVerified;
+                r.error = Some(format!("Attached, but the submission could not be checked: {}", e));
+            }
+            return Ok(SubmissionResult { success: false, files: results, submissions: Vec::new() });
+        }
+    };
+    for r in results.iter_mut() {
+        let file = r.file.as_ref().unwrap();
+        let found = submissions
+            .iter()
+            .any(|s| s.id == file.id || (!file.uuid.is_empty() && s.uuid == file.uuid));
+        r.status = if found { SubmissionFileStatus::Submitted } else { SubmissionFileStatus::NotVerified };
+    }
+    let success = results.iter().all(|r| r.status == SubmissionFileStatus::Submitted);
+    println!(
+        "[Desqta] Submitted {} file(s) to assessment {}{}",
+        ids.len(),
+        assessment_id,
+        if success { "" } else { " (not all verified)" }
+    );
+    Ok(SubmissionResult { success, files: results, submissions })
+}
+
+/// Abort a running upload. Returns false if no upload with that id is in progress.
+#[tauri::command]
+pub fn cancel_upload(id: String) -> bool {
+    match active().lock().unwrap().get(&id) {
+        Some(token) => {
+            token.cancel();
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accept(types: &[&str]) -> Vec<String> {
+        types.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn accepts_exact_types_wildcards_and_extensions() {
+        let accept = accept(&["application/pdf", "image/*", ".DOCX"]);
+        assert!(is_accepted(&accept, "application/pdf", "essay.pdf"));
+        assert!(is_accepted(&accept, "image/png", "diagram.png"));
+        assert!(is_accepted(&accept, "application/octet-stream", "Essay.docx"));
+        assert!(!is_accepted(&accept, "video/mp4", "clip.mp4"));
+        assert!(!is_accepted(&accept, "application/octet-stream", "essay.docx.zip"));
+    }
+
+    #[test]
+    fn wildcard_needs_the_whole_top_level_type() {
+        let accept = accept(&["image/*"]);
+        assert!(!is_accepted(&accept, "imagex/png", "a.png"));
+        assert!(is_accepted(&["*/*".to_string()], "video/mp4", "clip.mp4"));
+    }
+}
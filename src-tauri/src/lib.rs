@@ -8,14 +8,54 @@ mod login;
 
 #[path = "utils/netgrab.rs"]
 mod netgrab;
+#[path = "utils/neterror.rs"]
+mod neterror;
 #[path = "utils/settings.rs"]
 mod settings;
 #[path = "utils/analytics.rs"]
 mod analytics;
 #[path = "utils/session.rs"]
 mod session;
+#[path = "utils/crypto.rs"]
+mod crypto;
+#[path = "utils/heartbeat.rs"]
+mod heartbeat;
+#[path = "utils/seqta.rs"]
+mod seqta;
+#[path = "utils/db.rs"]
+mod db;
+#[path = "utils/search.rs"]
+mod search;
+#[path = "utils/feeds.rs"]
+mod feeds;
+#[path = "utils/feed_aggregator.rs"]
+mod feed_aggregator;
+#[path = "utils/opml.rs"]
+mod opml;
+#[path = "utils/article.rs"]
+mod article;
+#[path = "utils/sync.rs"]
+mod sync;
+#[path = "utils/notifier.rs"]
+mod notifier;
+#[path = "utils/reminders.rs"]
+mod reminders;
+#[path = "utils/ical.rs"]
+mod ical;
+#[path = "utils/calendar_feed.rs"]
+mod calendar_feed;
+#[path = "desqta-fs/downloads.rs"]
+mod downloads;
+#[path = "desqta-fs/uploads.rs"]
+mod uploads;
+#[path = "desqta-fs/course_export.rs"]
+mod course_export;
+#[path = "desqta-fs/report_archive.rs"]
+mod report_archive;
 
-use tauri::Manager;
+use std::sync::OnceLock;
+
+use tauri::{Emitter, Manager};
 use tauri::menu::{Menu, MenuItem, PredefinedMenuItem};
 use tauri::tray::TrayIconBuilder;
 use tauri::{AppHandle, WindowEvent, Window};
@@ -27,6 +67,18 @@ use tauri_plugin_dialog;
 
 use url::form_urlencoded::{byte_serialize, parse};
 
+static APP_HANDLE: OnceLock<AppHandle> = OnceLock::new();
+
+/// Tell the frontend that SEQTA rejected the saved session so it can prompt a new login.
+pub(crate) fn emit_session_expired() {
+    if let Some(app) = APP_HANDLE.get() {
+        println!("[Desqta] Session expired, notifying frontend");
+        if let Err(e) = app.emit("session-expired", ()) {
+            eprintln!("[Desqta] Failed to emit session-expired: {}", e);
+        }
+    }
+}
+
 /// Boilerplate example command
 #[tauri::command]
 fn greet(name: &str) -> String {
@@ -190,11 +242,67 @@ pub fn run() {
             is_autostart_enabled,
             netgrab::get_api_data,
             netgrab::open_url,
-            netgrab::get_rss_feed,
+            feeds::get_rss_feed,
+            article::get_feed_article,
+            feed_aggregator::get_aggregated_feed,
+            feed_aggregator::refresh_feeds,
+            feed_aggregator::get_feed_sources,
+            feed_aggregator::set_feed_item_read,
+            feed_aggregator::set_feed_item_starred,
+            feed_aggregator::mark_all_feed_items_read,
+            opml::import_opml,
+            opml::export_opml,
             netgrab::post_api_data,
             netgrab::fetch_api_data,
+            netgrab::fetch_api_response,
+            netgrab::clear_api_cache,
+            netgrab::validate_session,
             netgrab::get_seqta_file,
-            netgrab::upload_seqta_file,
+            uploads::upload_seqta_file,
+            uploads::cancel_upload,
+            uploads::submit_assessment,
+            course_export::export_course_resources,
+            course_export::cancel_course_export,
+            report_archive::list_reports,
+            report_archive::download_report,
+            report_archive::open_report,
+            report_archive::open_report_archive,
+            seqta::get_timetable,
+            seqta::get_upcoming_assessments,
+            seqta::get_past_assessments,
+            seqta::get_subjects,
+            seqta::get_notices,
+            seqta::get_notice_labels,
+            seqta::get_course,
+            seqta::get_reports,
+            seqta::get_messages,
+            seqta::get_submissions,
+            seqta::get_homework,
+            db::get_offline_timetable,
+            db::get_offline_assessments,
+            db::get_offline_subjects,
+            db::get_offline_notices,
+            db::get_offline_messages,
+            db::get_offline_reports,
+            db::get_sync_state,
+            db::get_last_synced,
+            search::search,
+            sync::sync_now,
+            reminders::list_reminders,
+            reminders::snooze_reminder,
+            reminders::dismiss_reminder,
+            ical::export_calendar,
+            calendar_feed::get_calendar_feed_urls,
+            calendar_feed::reset_calendar_feed_token,
+            downloads::download_seqta_file,
+            downloads::resume_download,
+            downloads::cancel_download,
+            downloads::list_downloads,
+            downloads::clear_download_history,
+            session::list_profiles,
+            session::switch_profile,
+            session::remove_profile,
+            session::rename_profile,
             login::check_session_exists,
             login::save_session,
             login::create_login_window,
@@ -213,8 +321,20 @@ pub fn run() {
             analytics::save_analytics,
             analytics::load_analytics,
             analytics::delete_analytics,
+            heartbeat::pause_heartbeat,
+            heartbeat::resume_heartbeat,
+            heartbeat::is_heartbeat_paused,
         ])
         .setup(|app| {
+            let _ = APP_HANDLE.set(app.handle().clone());
+            // Before any background task reads the session
+            session::Profiles::migrate_legacy_session();
+            heartbeat::start(app.handle().clone());
+            sync::start(app.handle().clone());
+            reminders::start(app.handle().clone());
+            feed_aggregator::start(app.handle().clone());
+            calendar_feed::apply(&settings::Settings::load());
+
             // Configure the existing main window
             if let Some(window) = app.webview_windows().get("main") {
                 let _ = window.set_title("DesQTA");
@@ -222,6 +342,12 @@ pub fn run() {
                 let _ = window.set_size(tauri::Size::Logical(tauri::LogicalSize::new(900.0, 700.0)));
                 let _ = window.set_decorations(false);
                 let _ = window.center();
+
+                // Autostart launches with --minimize; stay in the tray so reminders still fire
+                if std::env::args().any(|arg| arg == "--minimize") {
+                    println!("[Desqta] Started minimised");
+                    let _ = window.hide();
+                }
             }
 
             // Create tray menu
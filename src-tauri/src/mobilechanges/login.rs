@@ -15,28 +15,30 @@ pub fn check_session_exists() -> bool {
     session::Session::exists()
 }
 
-/// Persist the SEQTA `base_url` and `JSESSIONID`.
+/// Persist the SEQTA `base_url` and `JSESSIONID` as a profile.
 #[tauri::command]
 pub fn save_session(base_url: String, jsessionid: String) -> Result<(), String> {
-    session::Session {
+    session::Profiles::add_login(session::Session {
         base_url,
         jsessionid,
         additional_cookies: Vec::new(),
-    }
-    .save()
+        expires_at: None,
+        expired: false,
+    })
+    .map(|_| ())
     .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 pub fn logout() -> bool {
-    if let Ok(_) = session::Session::clear_file() {
+    if let Ok(_) = session::Session::clear() {
         true
     } else {
         false
     }
 }
 
-/// Open a login window and harvest the cookie once the user signs in.
+/// Open a login window and save the cookie as a profile once the user signs in.
 #[tauri::command]
 pub async fn create_login_window(app: tauri::AppHandle, url: String) -> Result<(), String> {
     use tauri::{WebviewUrl, WebviewWindowBuilder};
@@ -141,9 +143,11 @@ pub async fn create_login_window(app: tauri::AppHandle, url: String) -> Result<(
                                                 base_url,
                                                 jsessionid: value,
                                                 additional_cookies,
+                                                expires_at: Some(expire_time.unix_timestamp()),
+                                                expired: false,
                                             };
 
-                                            if let Err(err) = session.save() {
+                                            if let Err(err) = session::Profiles::add_login(session) {
                                                 eprintln!("Failed to save session: {}", err);
                                             }
 
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use std::{fs, path::PathBuf};
 
+use crate::session;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AssessmentData {
     pub id: i32,
@@ -22,12 +24,9 @@ pub struct AssessmentData {
     pub final_grade: Option<f32>,
 }
 
+/// Location: `$DATA_DIR/DesQTA/profiles/<id>/analytics.json`
 fn analytics_file() -> PathBuf {
-    let mut dir = dirs_next::data_dir().expect("Unable to determine data dir");
-    dir.push("DesQTA");
-    if !dir.exists() {
-        fs::create_dir_all(&dir).expect("Unable to create data dir");
-    }
+    let mut dir = session::profile_dir();
     dir.push("analytics.json");
     dir
 }
@@ -0,0 +1,166 @@
+use reqwest::header::CONTENT_TYPE;
+use scraper::{ElementRef, Html, Selector};
+use serde::Serialize;
+use std::collections::HashMap;
+use url::Url;
+
+use crate::feeds;
+use crate::neterror::NetError;
+
+/// The readable part of a web page, for the feed reader view.
+#[derive(Debug, Serialize)]
+pub struct Article {
+    /// Where the page ended up after redirects.
+    pub url: String,
+    pub title: Option<String>,
+    pub byline: Option<String>,
+    pub site_name: Option<String>,
+    pub excerpt: Option<String>,
+    /// Sanitised HTML of the main content.
+    pub content: String,
+    pub word_count: usize,
+}
+
+/// Class and id fragments that suggest an element holds the article, or holds page chrome.
+const POSITIVE: &[&str] = &["article", "body", "content", "entry", "main", "post", "story", "text"];
+const NEGATIVE: &[&str] = &[
+    "banner", "comment", "footer", "masthead", "menu", "nav", "promo", "related", "share",
+    "sidebar", "social", "sponsor", "subscribe", "widget",
+];
+
+/// Elements whose text counts towards their container's score.
+const PARAGRAPHS: &str = "p, pre, blockquote, td";
+
+fn selector(s: &str) -> Selector {
+    Selector::parse(s).expect("Invalid selector")
+}
+
+fn text_of(elem: ElementRef) -> String {
+    elem.text().flat_map(str::split_whitespace).collect::<Vec<_>>().join(" ")
+}
+
+fn non_empty(s: String) -> Option<String> {
+    let s = s.trim();
+    (!s.is_empty()).then(|| s.to_string())
+}
+
+/// `content` of the first `<meta>` with one of `names` as its `property` or `name`.
+fn meta(doc: &Html, names: &[&str]) -> Option<String> {
+    names.iter().find_map(|name| {
+        doc.select(&selector("meta"))
+            .find(|m| {
+                let attr = |a| m.value().attr(a).is_some_and(|v| v.eq_ignore_ascii_case(name));
+                attr("property") || attr("name")
+            })
+            .and_then(|m| m.value().attr("content"))
+            .and_then(|c| non_empty(c.to_string()))
+    })
+}
+
+fn class_weight(elem: ElementRef) -> f64 {
+    let names = format!("{} {}", elem.value().attr("class").unwrap_or(""), elem.value().id().unwrap_or(""))
+        .to_lowercase();
+    let mut weight = 0.0;
+    if POSITIVE.iter().any(|p| names.contains(p)) {
+        weight += 25.0;
+    }
+    if NEGATIVE.iter().any(|n| names.contains(n)) {
+        weight -= 25.0;
+    }
+    weight
+}
+
+/// Share of an element's text that sits inside links. Navigation and link lists are mostly links.
+fn link_density(elem: ElementRef) -> f64 {
+    let total = text_of(elem).len();
+    if total == 0 {
+        return 1.0;
+    }
+    let links: usize = elem.select(&selector("a")).map(|a| text_of(a).len()).sum();
+    links as f64 / total as f64
+}
+
+/// The element most likely to hold the article body, scored the way Readability does:
+/// each paragraph credits its parent in full and its grandparent by half, weighted by
+/// class names and discounted by link density.
+fn main_content(doc: &Html) -> Option<ElementRef<'_>> {
+    let mut scores = HashMap::new();
+    let mut elements = HashMap::new();
+    for paragraph in doc.select(&selector(PARAGRAPHS)) {
+        let text = text_of(paragraph);
+        if text.len() < 25 {
+            continue;
+        }
+        let score = 1.0 + text.matches(',').count() as f64 + (text.len() / 100).min(3) as f64;
+        let ancestors = paragraph.ancestors().filter_map(ElementRef::wrap).take(2);
+        for (depth, ancestor) in ancestors.enumerate() {
+            if matches!(ancestor.value().name(), "html" | "body") {
+                break;
+            }
+            let base = match ancestor.value().name() {
+                "article" => 10.0,
+                "div" | "section" | "main" => 5.0,
+                _ => 0.0,
+            };
+            *scores.entry(ancestor.id()).or_insert_with(|| base + class_weight(ancestor)) +=
+                score / (depth + 1) as f64;
+            elements.insert(ancestor.id(), ancestor);
+        }
+    }
+    scores
+        .into_iter()
+        .map(|(id, score)| (elements[&id], score * (1.0 - link_density(elements[&id]))))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(elem, _)| elem)
+        .or_else(|| doc.select(&selector("article, main, body")).next())
+}
+
+/// Pull the readable content out of `html`, fetched from `url`.
+pub(crate) fn extract(html: &str, url: &Url) -> Article {
+    let doc = Html::parse_document(html);
+    let title = meta(&doc, &["og:title", "twitter:title"]).or_else(|| {
+        doc.select(&selector("title, h1")).next().and_then(|t| non_empty(text_of(t)))
+    });
+    let content = main_content(&doc).map(|e| e.html()).unwrap_or_default();
+
+    let mut cleaner = feeds::sanitiser(Some(url));
+    cleaner
+        .rm_tags(["aside", "footer", "nav"])
+        .add_clean_content_tags(["aside", "button", "footer", "form", "iframe", "nav", "noscript", "select", "svg"]);
+    let content = cleaner.clean(&content).to_string();
+    let word_count = Html::parse_fragment(&content).root_element().text().flat_map(str::split_whitespace).count();
+
+    Article {
+        url: url.to_string(),
+        title,
+        byline: meta(&doc, &["author", "article:author", "byl"]),
+        site_name: meta(&doc, &["og:site_name", "application-name"]),
+        excerpt: meta(&doc, &["og:description", "description"]),
+        content,
+        word_count,
+    }
+}
+
+/// Fetch the page a feed item links to and return its main content for the reader view.
+#[tauri::command]
+pub async fn get_feed_article(url: String) -> Result<Article, NetError> {
+    let url = feeds::resolve_link(None, &url).ok_or_else(|| NetError::Rejected {
+        message: format!("Not a web address: {}", url),
+    })?;
+    let resp = feeds::client().get(&url).send().await?;
+    let status = resp.status();
+    let final_url = resp.url().clone();
+    let is_html = resp
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_none_or(|v| v.contains("html"));
+    let body = resp.text().await?;
+    if !status.is_success() {
+        return Err(NetError::http(status.as_u16(), body));
+    }
+    if !is_html {
+        return Err(NetError::decode(format!("{} is not a web page", final_url)));
+    }
+    Ok(extract(&body, &final_url))
+}
@@ -0,0 +1,235 @@
+use chrono::{Duration as ChronoDuration, Local};
+use rand::RngCore;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+use tokio::time::{timeout, Duration};
+
+use crate::crypto;
+use crate::ical;
+use crate::neterror::NetError;
+use crate::seqta;
+use crate::settings::Settings;
+
+/// Days of timetable served before and after today.
+const TIMETABLE_PAST_DAYS: i64 = 7;
+const TIMETABLE_FUTURE_DAYS: i64 = 8 * 7;
+/// Assessments served from this many days ago onwards.
+const ASSESSMENT_PAST_DAYS: i64 = 30;
+/// Requests are only ever a request line and a few headers.
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The running server's port and a way to stop it.
+static SERVER: Mutex<Option<(u16, oneshot::Sender<()>)>> = Mutex::new(None);
+
+/// Location: `$DATA_DIR/DesQTA/calendar_feed.token`
+fn token_file() -> PathBuf {
+    let mut dir = dirs_next::data_dir().expect("Unable to determine data dir");
+    dir.push("DesQTA");
+    if !dir.exists() {
+        std::fs::create_dir_all(&dir).expect("Unable to create data dir");
+    }
+    dir.push("calendar_feed.token");
+    dir
+}
+
+fn new_token() -> Result<String, String> {
+    let mut bytes = [0u8; 16];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    crypto::write_encrypted(&token_file(), &token)
+        .map_err(|e| format!("Failed to save calendar feed token: {}", e))?;
+    Ok(token)
+}
+
+/// The per-install token that must prefix every feed path. A token that exists but cannot
+/// be read (e.g. the keyring is still locked) is an error rather than replaced, since
+/// replacing it would break every calendar already subscribed.
+fn token() -> Result<String, String> {
+    let path = token_file();
+    if !path.exists() {
+        return new_token();
+    }
+    crypto::read_encrypted(&path)
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .ok_or_else(|| "Calendar feed token cannot be read".to_string())
+}
+
+/// Compare without bailing out at the first differing byte.
+fn token_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len() && given.bytes().zip(expected.bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+fn date_string(days_from_today: i64) -> String {
+    (Local::now().date_naive() + ChronoDuration::days(days_from_today))
+        .format("%Y-%m-%d")
+        .to_string()
+}
+
+async fn timetable_calendar() -> Result<String, NetError> {
+    let lessons = seqta::timetable(&date_string(-TIMETABLE_PAST_DAYS), &date_string(TIMETABLE_FUTURE_DAYS), false).await?;
+    Ok(ical::build_calendar("SEQTA Timetable", &lessons, &[]))
+}
+
+async fn assessments_calendar() -> Result<String, NetError> {
+    let assessments = ical::assessments_between(&date_string(-ASSESSMENT_PAST_DAYS), "").await?;
+    Ok(ical::build_calendar("SEQTA Assessments", &[], &assessments))
+}
+
+fn response(status: &str, content_type: &str, body: &str, head_only: bool) -> Vec<u8> {
+    let mut out = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )
+    .into_bytes();
+    if !head_only {
+        out.extend_from_slice(body.as_bytes());
+    }
+    out
+}
+
+async fn respond(request_line: &str) -> Vec<u8> {
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let head_only = method == "HEAD";
+    if method != "GET" && !head_only {
+        return response("405 Method Not Allowed", "text/plain", "Method not allowed", false);
+    }
+
+    // Expected form: /<token>/<feed>.ics
+    let path = target.split('?').next().unwrap_or("");
+    let mut segments = path.trim_start_matches('/').splitn(2, '/');
+    let (given, feed) = (segments.next().unwrap_or(""), segments.next().unwrap_or(""));
+    let expected = match token() {
+        Ok(token) => token,
+        Err(e) => {
+            eprintln!("[Desqta] Calendar feed unavailable: {}", e);
+            return response("503 Service Unavailable", "text/plain", "Calendar feed unavailable", head_only);
+        }
+    };
+    if !token_matches(given, &expected) {
+        return response("404 Not Found", "text/plain", "Not found", head_only);
+    }
+
+    let calendar = match feed {
+        "timetable.ics" => timetable_calendar().await,
+        "assessments.ics" => assessments_calendar().await,
+        _ => return response("404 Not Found", "text/plain", "Not found", head_only),
+    };
+    match calendar {
+        Ok(body) => response("200 OK", "text/calendar; charset=utf-8", &body, head_only),
+        Err(e) => {
+            eprintln!("[Desqta] Calendar feed {} failed: {}", feed, e);
+            response("503 Service Unavailable", "text/plain", &e.to_string(), head_only)
+        }
+    }
+}
+
+async fn handle(mut stream: TcpStream) {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    let read = timeout(REQUEST_TIMEOUT, async {
+        while !buf.windows(4).any(|w| w == b"\r\n\r\n") && buf.len() < MAX_REQUEST_BYTES {
+            match stream.read(&mut chunk).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => buf.extend_from_slice(&chunk[..n]),
+            }
+        }
+    })
+    .await;
+    if read.is_err() || buf.is_empty() {
+        return;
+    }
+
+    let request = String::from_utf8_lossy(&buf);
+    let request_line = request.lines().next().unwrap_or("");
+    let reply = respond(request_line).await;
+    let _ = stream.write_all(&reply).await;
+    let _ = stream.shutdown().await;
+}
+
+async fn serve(listener: TcpListener, mut shutdown: oneshot::Receiver<()>) {
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => {
+                    tauri::async_runtime::spawn(handle(stream));
+                }
+                Err(e) => eprintln!("[Desqta] Calendar feed accept failed: {}", e),
+            },
+        }
+    }
+}
+
+/// Start, stop or move the feed server to match the current settings.
+pub fn apply(settings: &Settings) {
+    let mut server = SERVER.lock().unwrap_or_else(|e| e.into_inner());
+    let wanted = settings.calendar_feed_enabled.then_some(settings.calendar_feed_port);
+    if server.as_ref().map(|(port, _)| *port) == wanted {
+        return;
+    }
+
+    if let Some((port, stop)) = server.take() {
+        let _ = stop.send(());
+        println!("[Desqta] Calendar feed stopped on port {}", port);
+    }
+    let Some(port) = wanted else { return };
+
+    let (stop, shutdown) = oneshot::channel();
+    *server = Some((port, stop));
+    tauri::async_runtime::spawn(async move {
+        // Loopback only; the feed is never reachable from other machines
+        match TcpListener::bind(("127.0.0.1", port)).await {
+            Ok(listener) => {
+                println!("[Desqta] Calendar feed listening on 127.0.0.1:{}", port);
+                serve(listener, shutdown).await;
+            }
+            Err(e) => {
+                eprintln!("[Desqta] Failed to start calendar feed on port {}: {}", port, e);
+                let mut server = SERVER.lock().unwrap_or_else(|e| e.into_inner());
+                if server.as_ref().is_some_and(|(p, _)| *p == port) {
+                    *server = None;
+                }
+            }
+        }
+    });
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CalendarFeedUrls {
+    pub running: bool,
+    pub timetable: String,
+    pub assessments: String,
+}
+
+fn feed_urls() -> Result<CalendarFeedUrls, String> {
+    let settings = Settings::load();
+    let running = SERVER.lock().unwrap_or_else(|e| e.into_inner()).is_some();
+    let base = format!("webcal://127.0.0.1:{}/{}", settings.calendar_feed_port, token()?);
+    Ok(CalendarFeedUrls {
+        running,
+        timetable: format!("{}/timetable.ics", base),
+        assessments: format!("{}/assessments.ics", base),
+    })
+}
+
+/// Subscription URLs for calendar apps.
+#[tauri::command]
+pub fn get_calendar_feed_urls() -> Result<CalendarFeedUrls, String> {
+    feed_urls()
+}
+
+/// Replace the token, so existing subscription URLs stop working.
+#[tauri::command]
+pub fn reset_calendar_feed_token() -> Result<CalendarFeedUrls, String> {
+    new_token()?;
+    feed_urls()
+}
@@ -0,0 +1,254 @@
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::{engine::general_purpose, Engine as _};
+use rand::RngCore;
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// Marks a value as encrypted by this module, so plaintext written by older
+/// versions can be told apart and migrated.
+const ENCRYPTED_PREFIX: &str = "desqta-enc:v1:";
+
+#[cfg(desktop)]
+const KEYRING_SERVICE: &str = "com.desqta.app";
+#[cfg(desktop)]
+const KEYRING_USER: &str = "storage-key";
+
+/// Set this to keep the key in `storage.key` instead of the OS keyring (headless Linux, testing).
+#[cfg(desktop)]
+const DISABLE_KEYRING_ENV: &str = "DESQTA_DISABLE_KEYRING";
+
+static STORAGE_KEY: OnceLock<[u8; 32]> = OnceLock::new();
+
+/// Location: `$DATA_DIR/DesQTA/storage.key`
+/// Only used when the OS keyring is unavailable.
+fn key_file() -> PathBuf {
+    let mut dir = dirs_next::data_dir().expect("Unable to determine data dir");
+    dir.push("DesQTA");
+    if !dir.exists() {
+        fs::create_dir_all(&dir).expect("Unable to create data dir");
+    }
+    dir.push("storage.key");
+    dir
+}
+
+/// The per-install key. Only a key that was found or created is cached, so a keyring that
+/// was still locked is asked again on the next call.
+fn storage_key() -> Result<&'static [u8; 32], String> {
+    if let Some(key) = STORAGE_KEY.get() {
+        return Ok(key);
+    }
+    let key = load_or_create_key()?;
+    Ok(STORAGE_KEY.get_or_init(|| key))
+}
+
+/// What the OS keyring said when asked for the storage key.
+#[derive(Debug)]
+enum KeyringRead {
+    Found([u8; 32]),
+    /// The keyring works but holds no key yet.
+    Missing,
+    /// The keyring could not be read, e.g. Secret Service is still locked at login.
+    Unavailable(String),
+}
+
+#[derive(Debug, PartialEq)]
+enum KeyChoice {
+    Use([u8; 32]),
+    Create,
+    Unavailable(String),
+}
+
+/// Pick the storage key. A new key is only created when there is definitely none, since
+/// a new key makes every encrypted file unreadable.
+fn choose_key(keyring: KeyringRead, file: Option<[u8; 32]>) -> KeyChoice {
+    match (keyring, file) {
+        (KeyringRead::Found(key), _) => KeyChoice::Use(key),
+        (_, Some(key)) => KeyChoice::Use(key),
+        (KeyringRead::Missing, None) => KeyChoice::Create,
+        (KeyringRead::Unavailable(e), None) => KeyChoice::Unavailable(e),
+    }
+}
+
+fn load_or_create_key() -> Result<[u8; 32], String> {
+    #[cfg(desktop)]
+    let use_keyring = std::env::var_os(DISABLE_KEYRING_ENV).is_none();
+
+    #[cfg(desktop)]
+    let keyring = if use_keyring { keyring_key() } else { KeyringRead::Missing };
+    #[cfg(not(desktop))]
+    let keyring = KeyringRead::Missing;
+
+    match choose_key(keyring, file_key()) {
+        KeyChoice::Use(key) => return Ok(key),
+        KeyChoice::Unavailable(e) => {
+            eprintln!("[Desqta] Storage key unavailable, not creating a new one: {}", e);
+            return Err(format!("Storage key unavailable: {}", e));
+        }
+        KeyChoice::Create => {}
+    }
+
+    let mut key = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut key);
+
+    #[cfg(desktop)]
+    if use_keyring {
+        let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER);
+        match entry.and_then(|e| e.set_password(&general_purpose::STANDARD.encode(key))) {
+            Ok(()) => return Ok(key),
+            Err(e) => eprintln!("[Desqta] OS keyring unavailable, falling back to key file: {}", e),
+        }
+    }
+
+    if let Err(e) = write_key_file(&key) {
+        eprintln!("[Desqta] Failed to persist storage key: {}", e);
+    }
+    Ok(key)
+}
+
+#[cfg(desktop)]
+fn keyring_key() -> KeyringRead {
+    let entry = match keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER) {
+        Ok(entry) => entry,
+        Err(e) => return KeyringRead::Unavailable(e.to_string()),
+    };
+    match entry.get_password() {
+        Ok(encoded) => match decode_key(&encoded) {
+            Some(key) => KeyringRead::Found(key),
+            None => KeyringRead::Unavailable("stored key is malformed".to_string()),
+        },
+        Err(keyring::Error::NoEntry) => KeyringRead::Missing,
+        Err(e) => {
+            eprintln!("[Desqta] Failed to read storage key from OS keyring: {}", e);
+            KeyringRead::Unavailable(e.to_string())
+        }
+    }
+}
+
+fn file_key() -> Option<[u8; 32]> {
+    let encoded = fs::read_to_string(key_file()).ok()?;
+    decode_key(encoded.trim())
+}
+
+fn decode_key(encoded: &str) -> Option<[u8; 32]> {
+    general_purpose::STANDARD.decode(encoded).ok()?.try_into().ok()
+}
+
+fn write_key_file(key: &[u8; 32]) -> io::Result<()> {
+    let path = key_file();
+    fs::write(&path, general_purpose::STANDARD.encode(key))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
+
+/// True if `value` was produced by `encrypt_string`.
+pub fn is_encrypted(value: &str) -> bool {
+    value.trim_start().starts_with(ENCRYPTED_PREFIX)
+}
+
+/// Encrypt with AES-256-GCM under the per-install key.
+/// Output is `desqta-enc:v1:` followed by base64 of the nonce and ciphertext.
+/// Fails if the storage key cannot be loaded, so nothing is written under a wrong key.
+pub fn encrypt_string(plaintext: &str) -> Result<String, String> {
+    let cipher = Aes256Gcm::new_from_slice(storage_key()?).expect("Storage key is 32 bytes");
+    let mut nonce = [0u8; 12];
+    rand::rngs::OsRng.fill_bytes(&mut nonce);
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+        .expect("AES-GCM encryption cannot fail for in-memory data");
+
+    let mut payload = nonce.to_vec();
+    payload.extend_from_slice(&ciphertext);
+    Ok(format!("{}{}", ENCRYPTED_PREFIX, general_purpose::STANDARD.encode(payload)))
+}
+
+/// Decrypt a value produced by `encrypt_string`.
+pub fn decrypt_string(value: &str) -> Result<String, String> {
+    let encoded = value
+        .trim()
+        .strip_prefix(ENCRYPTED_PREFIX)
+        .ok_or("Value is not encrypted")?;
+    let payload = general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|e| format!("Invalid encrypted value: {}", e))?;
+    if payload.len() < 12 {
+        return Err("Invalid encrypted value: too short".to_string());
+    }
+
+    let (nonce, ciphertext) = payload.split_at(12);
+    let cipher = Aes256Gcm::new_from_slice(storage_key()?).expect("Storage key is 32 bytes");
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Failed to decrypt value (storage key changed?)".to_string())?;
+    String::from_utf8(plaintext).map_err(|e| e.to_string())
+}
+
+/// Read a file written by `write_encrypted`.
+/// Plaintext files from older versions are returned as-is and rewritten encrypted.
+pub fn read_encrypted(path: &Path) -> Option<String> {
+    let contents = fs::read_to_string(path).ok()?;
+    if is_encrypted(&contents) {
+        return match decrypt_string(&contents) {
+            Ok(plaintext) => Some(plaintext),
+            Err(e) => {
+                eprintln!("[Desqta] Failed to read {}: {}", path.display(), e);
+                None
+            }
+        };
+    }
+
+    if let Err(e) = write_encrypted(path, &contents) {
+        eprintln!("[Desqta] Failed to encrypt {}: {}", path.display(), e);
+    }
+    Some(contents)
+}
+
+/// Encrypt `contents` and write them to `path`. The file is left untouched if the
+/// storage key is unavailable, and is replaced in one step so a crash mid-write cannot
+/// leave it truncated.
+pub fn write_encrypted(path: &Path, contents: &str) -> io::Result<()> {
+    let encrypted = encrypt_string(contents).map_err(io::Error::other)?;
+    write_atomic(path, encrypted.as_bytes())
+}
+
+/// Write `contents` to `<path>.tmp` and rename it over `path`, so readers see either the
+/// old file or the new one.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyring_error_does_not_rotate_the_key() {
+        let file = [7u8; 32];
+        let error = || KeyringRead::Unavailable("org.freedesktop.DBus.Error.ServiceUnknown".to_string());
+        assert_eq!(choose_key(error(), Some(file)), KeyChoice::Use(file));
+        assert!(matches!(choose_key(error(), None), KeyChoice::Unavailable(_)));
+    }
+
+    #[test]
+    fn key_is_only_created_when_there_is_none() {
+        let stored = [1u8; 32];
+        let file = [2u8; 32];
+        assert_eq!(choose_key(KeyringRead::Found(stored), Some(file)), KeyChoice::Use(stored));
+        assert_eq!(choose_key(KeyringRead::Missing, Some(file)), KeyChoice::Use(file));
+        assert_eq!(choose_key(KeyringRead::Missing, None), KeyChoice::Create);
+    }
+}
@@ -0,0 +1,522 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use serde_json::Value;
+use std::{path::PathBuf, sync::Mutex};
+
+use crate::seqta::{
+    self, Assessment, MessageParticipant, MessageSummary, MessagesPayload, Notice, PastAssessmentsPayload,
+    Report, Subject, SubjectFolder, TimetableLesson, TimetablePayload,
+};
+use crate::session;
+
+/// Bump when `SCHEMA` changes; older databases are rebuilt from scratch since
+/// everything in them can be fetched again.
+const SCHEMA_VERSION: i32 = 3;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS lessons (
+        date TEXT NOT NULL,
+        from_time TEXT NOT NULL,
+        until TEXT NOT NULL,
+        code TEXT NOT NULL,
+        description TEXT NOT NULL,
+        staff TEXT,
+        room TEXT,
+        period TEXT,
+        metaclass INTEGER,
+        programme INTEGER,
+        PRIMARY KEY (date, from_time, code)
+    );
+    CREATE TABLE IF NOT EXISTS assessments (
+        id INTEGER PRIMARY KEY,
+        title TEXT NOT NULL,
+        subject TEXT NOT NULL,
+        code TEXT NOT NULL,
+        due TEXT NOT NULL,
+        status TEXT,
+        metaclass INTEGER NOT NULL,
+        programme INTEGER NOT NULL,
+        colour TEXT,
+        upcoming INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE IF NOT EXISTS subjects (
+        metaclass INTEGER NOT NULL,
+        programme INTEGER NOT NULL,
+        code TEXT NOT NULL,
+        classunit INTEGER NOT NULL,
+        title TEXT NOT NULL,
+        description TEXT NOT NULL,
+        marksbook_type TEXT,
+        folder_id INTEGER NOT NULL,
+        folder_code TEXT NOT NULL,
+        folder_description TEXT NOT NULL,
+        folder_active INTEGER NOT NULL,
+        position INTEGER NOT NULL,
+        PRIMARY KEY (metaclass, programme)
+    );
+    CREATE TABLE IF NOT EXISTS notices (
+        date TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        title TEXT NOT NULL,
+        label_title TEXT NOT NULL,
+        staff TEXT NOT NULL,
+        colour TEXT,
+        label INTEGER,
+        contents TEXT NOT NULL,
+        PRIMARY KEY (date, position)
+    );
+    CREATE TABLE IF NOT EXISTS messages (
+        id INTEGER NOT NULL,
+        label TEXT NOT NULL,
+        subject TEXT NOT NULL,
+        sender TEXT NOT NULL,
+        date TEXT NOT NULL,
+        read INTEGER NOT NULL,
+        attachments INTEGER NOT NULL,
+        participants TEXT NOT NULL,
+        PRIMARY KEY (id, label)
+    );
+    CREATE TABLE IF NOT EXISTS reports (
+        uuid TEXT PRIMARY KEY,
+        year TEXT NOT NULL,
+        terms TEXT NOT NULL,
+        types TEXT NOT NULL,
+        created_date TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS sync_state (
+        dataset TEXT PRIMARY KEY,
+        last_synced INTEGER NOT NULL
+    );
+    CREATE VIRTUAL TABLE IF NOT EXISTS search_index USING fts5(
+        kind UNINDEXED,
+        key UNINDEXED,
+        title,
+        body,
+        subject,
+        date UNINDEXED,
+        link UNINDEXED,
+        tokenize = 'porter unicode61 remove_diacritics 2'
+    );
+    CREATE TABLE IF NOT EXISTS search_keys (
+        kind TEXT NOT NULL,
+        key TEXT NOT NULL,
+        doc INTEGER NOT NULL,
+        PRIMARY KEY (kind, key)
+    );
+";
+
+/// Open connection and the file it belongs to, so switching profile reopens it.
+static DB: Mutex<Option<(PathBuf, Connection)>> = Mutex::new(None);
+
+/// Location: `$DATA_DIR/DesQTA/profiles/<id>/desqta.db`
+fn db_file() -> PathBuf {
+    let mut dir = session::profile_dir();
+    dir.push("desqta.db");
+    dir
+}
+
+fn open(path: &PathBuf) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+
+    let version: i32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version != SCHEMA_VERSION {
+        if version != 0 {
+            println!("[Desqta] Rebuilding offline database (schema {} -> {})", version, SCHEMA_VERSION);
+            conn.execute_batch(
+                "DROP TABLE IF EXISTS lessons; DROP TABLE IF EXISTS assessments;
+                 DROP TABLE IF EXISTS subjects; DROP TABLE IF EXISTS notices;
+                 DROP TABLE IF EXISTS messages; DROP TABLE IF EXISTS reports;
+                 DROP TABLE IF EXISTS sync_state; DROP TABLE IF EXISTS search_index;
+                 DROP TABLE IF EXISTS search_keys;",
+            )?;
+        }
+        conn.execute_batch(SCHEMA)?;
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    }
+    Ok(conn)
+}
+
+/// Run `f` against the active profile's database.
+pub(crate) fn with_db<T>(f: impl FnOnce(&mut Connection) -> rusqlite::Result<T>) -> Result<T, String> {
+    let path = db_file();
+    let mut guard = DB.lock().map_err(|e| e.to_string())?;
+    if guard.as_ref().is_none_or(|(open_path, _)| open_path != &path) {
+        let conn = open(&path).map_err(|e| format!("Failed to open offline database: {}", e))?;
+        *guard = Some((path, conn));
+    }
+    let (_, conn) = guard.as_mut().unwrap();
+    f(conn).map_err(|e| e.to_string())
+}
+
+/// Drop the open connection, e.g. before the profile's directory is deleted.
+pub(crate) fn close() {
+    if let Ok(mut guard) = DB.lock() {
+        *guard = None;
+    }
+}
+
+fn now_secs() -> i64 {
+    time::OffsetDateTime::now_utc().unix_timestamp()
+}
+
+fn mark_synced(conn: &Connection, dataset: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO sync_state (dataset, last_synced) VALUES (?1, ?2)
+         ON CONFLICT(dataset) DO UPDATE SET last_synced = excluded.last_synced",
+        params![dataset, now_secs()],
+    )?;
+    Ok(())
+}
+
+/// Store a successful SEQTA response, if it is one of the datasets kept offline.
+/// Called by netgrab for every network response; failures are logged and ignored.
+pub(crate) fn ingest(path: &str, body: Option<&Value>, data: &str) {
+    let field = |name: &str| body.and_then(|b| b.get(name)).and_then(|v| v.as_str()).map(|s| s.to_string());
+
+    let result = if path.contains("/load/timetable") {
+        match (field("from"), field("until")) {
+            (Some(from), Some(until)) => seqta::decode_payload::<TimetablePayload>(path, data)
+                .map_err(|e| e.to_string())
+                .and_then(|p| store_timetable(&from, &until, &p.items)),
+            _ => Ok(()),
+        }
+    } else if path.contains("/assessment/list/upcoming") {
+        seqta::decode_payload::<Vec<Assessment>>(path, data)
+            .map_err(|e| e.to_string())
+            .and_then(|a| store_assessments(&a, true))
+    } else if path.contains("/assessment/list/past") {
+        seqta::decode_payload::<PastAssessmentsPayload>(path, data)
+            .map_err(|e| e.to_string())
+            .and_then(|p| store_assessments(&p.tasks, false))
+    } else if path.contains("/load/subjects") {
+        seqta::decode_payload::<Vec<SubjectFolder>>(path, data)
+            .map_err(|e| e.to_string())
+            .and_then(|f| store_subjects(&f))
+    } else if path.contains("/load/notices") {
+        match field("date") {
+            Some(date) => seqta::decode_payload::<Vec<Notice>>(path, data)
+                .map_err(|e| e.to_string())
+                .and_then(|n| store_notices(&date, &n)),
+            // `{ mode: "labels" }` requests return labels, not notices
+            None => Ok(()),
+        }
+    } else if path.ends_with("/load/message") {
+        match (field("action").as_deref(), field("label")) {
+            (Some("list"), Some(label)) => seqta::decode_payload::<MessagesPayload>(path, data)
+                .map_err(|e| e.to_string())
+                .and_then(|p| store_messages(&label, &p.messages)),
+            _ => Ok(()),
+        }
+    } else if path.contains("/load/reports") {
+        seqta::decode_payload::<Vec<Report>>(path, data)
+            .map_err(|e| e.to_string())
+            .and_then(|r| store_reports(&r))
+    } else {
+        Ok(())
+    };
+
+    if let Err(e) = result {
+        eprintln!("[Desqta] Failed to store {} offline: {}", path, e);
+    }
+}
+
+fn store_timetable(from: &str, until: &str, lessons: &[TimetableLesson]) -> Result<(), String> {
+    with_db(|conn| {
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM lessons WHERE date BETWEEN ?1 AND ?2", params![from, until])?;
+        for l in lessons {
+            tx.execute(
+                "INSERT OR REPLACE INTO lessons
+                 (date, from_time, until, code, description, staff, room, period, metaclass, programme)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                params![l.date, l.from, l.until, l.code, l.description, l.staff, l.room, l.period, l.metaclass, l.programme],
+            )?;
+        }
+        mark_synced(&tx, "timetable")?;
+        tx.commit()
+    })
+}
+
+fn store_assessments(assessments: &[Assessment], upcoming: bool) -> Result<(), String> {
+    with_db(|conn| {
+        let tx = conn.transaction()?;
+        if upcoming {
+            // Anything no longer in the upcoming list has been handed in or marked
+            tx.execute("UPDATE assessments SET upcoming = 0", [])?;
+        }
+        for a in assessments {
+            tx.execute(
+                "INSERT INTO assessments
+                 (id, title, subject, code, due, status, metaclass, programme, colour, upcoming)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                 ON CONFLICT(id) DO UPDATE SET
+                   title = excluded.title, subject = excluded.subject, code = excluded.code,
+                   due = excluded.due, status = excluded.status, metaclass = excluded.metaclass,
+                   programme = excluded.programme, colour = COALESCE(excluded.colour, colour),
+                   upcoming = excluded.upcoming",
+                params![a.id, a.title, a.subject, a.code, a.due, a.status, a.metaclass_id, a.programme_id, a.colour, upcoming],
+            )?;
+        }
+        mark_synced(&tx, "assessments")?;
+        tx.commit()
+    })
+}
+
+fn store_subjects(folders: &[SubjectFolder]) -> Result<(), String> {
+    with_db(|conn| {
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM subjects", [])?;
+        let mut position = 0;
+        for folder in folders {
+            for s in &folder.subjects {
+                tx.execute(
+                    "INSERT OR REPLACE INTO subjects
+                     (metaclass, programme, code, classunit, title, description, marksbook_type,
+                      folder_id, folder_code, folder_description, folder_active, position)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                    params![
+                        s.metaclass, s.programme, s.code, s.classunit, s.title, s.description, s.marksbook_type,
+                        folder.id, folder.code, folder.description, folder.active, position
+                    ],
+                )?;
+                position += 1;
+            }
+        }
+        mark_synced(&tx, "subjects")?;
+        tx.commit()
+    })
+}
+
+fn store_notices(date: &str, notices: &[Notice]) -> Result<(), String> {
+    with_db(|conn| {
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM notices WHERE date = ?1", params![date])?;
+        for (position, n) in notices.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO notices (date, position, title, label_title, staff, colour, label, contents)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![date, position as i64, n.title, n.label_title, n.staff, n.colour, n.label, n.contents],
+            )?;
+        }
+        mark_synced(&tx, "notices")?;
+        tx.commit()
+    })
+}
+
+fn store_messages(label: &str, messages: &[MessageSummary]) -> Result<(), String> {
+    with_db(|conn| {
+        let tx = conn.transaction()?;
+        for m in messages {
+            let participants = serde_json::to_string(&m.participants).unwrap_or_else(|_| "[]".to_string());
+            tx.execute(
+                "INSERT OR REPLACE INTO messages (id, label, subject, sender, date, read, attachments, participants)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![m.id, label, m.subject, m.sender, m.date, m.read, m.attachments, participants],
+            )?;
+        }
+        mark_synced(&tx, "messages")?;
+        tx.commit()
+    })
+}
+
+fn store_reports(reports: &[Report]) -> Result<(), String> {
+    with_db(|conn| {
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM reports", [])?;
+        for r in reports {
+            tx.execute(
+                "INSERT OR REPLACE INTO reports (uuid, year, terms, types, created_date) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![r.uuid, r.year, r.terms, r.types, r.created_date],
+            )?;
+        }
+        mark_synced(&tx, "reports")?;
+        tx.commit()
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct SyncState {
+    pub dataset: String,
+    /// Unix timestamp (seconds) of the last successful fetch.
+    pub last_synced: i64,
+}
+
+/// Lessons stored for `from`..=`until` (`YYYY-MM-DD`).
+#[tauri::command]
+pub fn get_offline_timetable(from: String, until: String) -> Result<Vec<TimetableLesson>, String> {
+    with_db(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT date, from_time, until, code, description, staff, room, period, metaclass, programme
+             FROM lessons WHERE date BETWEEN ?1 AND ?2 ORDER BY date, from_time",
+        )?;
+        let rows = stmt.query_map(params![from, until], |row| {
+            Ok(TimetableLesson {
+                date: row.get(0)?,
+                from: row.get(1)?,
+                until: row.get(2)?,
+                code: row.get(3)?,
+                description: row.get(4)?,
+                staff: row.get(5)?,
+                room: row.get(6)?,
+                period: row.get(7)?,
+                metaclass: row.get(8)?,
+                programme: row.get(9)?,
+            })
+        })?;
+        rows.collect()
+    })
+}
+
+/// Stored assessments, soonest due first. Only ones still on the upcoming list if `upcoming_only`.
+#[tauri::command]
+pub fn get_offline_assessments(upcoming_only: bool) -> Result<Vec<Assessment>, String> {
+    with_db(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT id, title, subject, code, due, status, metaclass, programme, colour
+             FROM assessments WHERE upcoming = 1 OR ?1 = 0 ORDER BY due",
+        )?;
+        let rows = stmt.query_map(params![upcoming_only], |row| {
+            Ok(Assessment {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                subject: row.get(2)?,
+                code: row.get(3)?,
+                due: row.get(4)?,
+                status: row.get(5)?,
+                metaclass_id: row.get(6)?,
+                programme_id: row.get(7)?,
+                colour: row.get(8)?,
+            })
+        })?;
+        rows.collect()
+    })
+}
+
+#[tauri::command]
+pub fn get_offline_subjects() -> Result<Vec<SubjectFolder>, String> {
+    with_db(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT folder_id, folder_code, folder_description, folder_active,
+                    metaclass, programme, code, classunit, title, description, marksbook_type
+             FROM subjects ORDER BY position",
+        )?;
+        let mut rows = stmt.query([])?;
+        let mut folders: Vec<SubjectFolder> = Vec::new();
+        while let Some(row) = rows.next()? {
+            let folder_id: i64 = row.get(0)?;
+            if folders.last().is_none_or(|f| f.id != folder_id) {
+                folders.push(SubjectFolder {
+                    id: folder_id,
+                    code: row.get(1)?,
+                    description: row.get(2)?,
+                    active: row.get(3)?,
+                    subjects: Vec::new(),
+                });
+            }
+            folders.last_mut().unwrap().subjects.push(Subject {
+                metaclass: row.get(4)?,
+                programme: row.get(5)?,
+                code: row.get(6)?,
+                classunit: row.get(7)?,
+                title: row.get(8)?,
+                description: row.get(9)?,
+                marksbook_type: row.get(10)?,
+            });
+        }
+        Ok(folders)
+    })
+}
+
+/// Notices stored for `date` (`YYYY-MM-DD`).
+#[tauri::command]
+pub fn get_offline_notices(date: String) -> Result<Vec<Notice>, String> {
+    with_db(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT title, label_title, staff, colour, label, contents
+             FROM notices WHERE date = ?1 ORDER BY position",
+        )?;
+        let rows = stmt.query_map(params![date], |row| {
+            Ok(Notice {
+                id: None,
+                title: row.get(0)?,
+                label_title: row.get(1)?,
+                staff: row.get(2)?,
+                colour: row.get(3)?,
+                label: row.get(4)?,
+                contents: row.get(5)?,
+            })
+        })?;
+        rows.collect()
+    })
+}
+
+/// Stored messages in a folder (`inbox`, `sent` or `outbox`), newest first.
+#[tauri::command]
+pub fn get_offline_messages(label: String) -> Result<Vec<MessageSummary>, String> {
+    with_db(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT id, subject, sender, date, read, attachments, participants
+             FROM messages WHERE label = ?1 ORDER BY date DESC",
+        )?;
+        let rows = stmt.query_map(params![label], |row| {
+            let participants: String = row.get(6)?;
+            Ok(MessageSummary {
+                id: row.get(0)?,
+                subject: row.get(1)?,
+                sender: row.get(2)?,
+                date: row.get(3)?,
+                read: row.get(4)?,
+                attachments: row.get(5)?,
+                participants: serde_json::from_str::<Vec<MessageParticipant>>(&participants).unwrap_or_default(),
+            })
+        })?;
+        rows.collect()
+    })
+}
+
+#[tauri::command]
+pub fn get_offline_reports() -> Result<Vec<Report>, String> {
+    with_db(|conn| {
+        let mut stmt = conn.prepare("SELECT uuid, year, terms, types, created_date FROM reports ORDER BY year DESC")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(Report {
+                uuid: row.get(0)?,
+                year: row.get(1)?,
+                terms: row.get(2)?,
+                types: row.get(3)?,
+                created_date: row.get(4)?,
+            })
+        })?;
+        rows.collect()
+    })
+}
+
+/// When each dataset (`timetable`, `assessments`, `subjects`, `notices`, `messages`,
+/// `reports`) was last fetched from SEQTA.
+#[tauri::command]
+pub fn get_sync_state() -> Result<Vec<SyncState>, String> {
+    with_db(|conn| {
+        let mut stmt = conn.prepare("SELECT dataset, last_synced FROM sync_state ORDER BY dataset")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(SyncState {
+                dataset: row.get(0)?,
+                last_synced: row.get(1)?,
+            })
+        })?;
+        rows.collect()
+    })
+}
+
+/// Last sync time of one dataset, if it has ever been fetched.
+#[tauri::command]
+pub fn get_last_synced(dataset: String) -> Result<Option<i64>, String> {
+    with_db(|conn| {
+        conn.query_row(
+            "SELECT last_synced FROM sync_state WHERE dataset = ?1",
+            params![dataset],
+            |row| row.get(0),
+        )
+        .optional()
+    })
+}
@@ -0,0 +1,398 @@
+use chrono::{DateTime, Utc};
+use futures_util::future::join_all;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter};
+use tokio::time::{sleep, Duration};
+
+use crate::feeds::{self, FeedItem, FeedResponse};
+use crate::neterror::NetError;
+use crate::opml;
+use crate::session;
+use crate::settings::Settings;
+
+/// Items kept per feed, newest first. Starred items are never dropped.
+const MAX_ITEMS_PER_FEED: usize = 200;
+/// Item ids remembered per feed, so an item pruned from the store is not added again as
+/// unread while the feed still lists it.
+const MAX_SEEN_PER_FEED: usize = 1000;
+/// Give the app a moment to start before the first poll.
+const STARTUP_DELAY: Duration = Duration::from_secs(10);
+
+/// Serialises read-modify-write of `feeds.json`.
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+/// What we remember about each subscribed feed between polls.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct SourceState {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    etag: Option<String>,
+    #[serde(default)]
+    last_modified: Option<String>,
+    /// RFC 3339
+    #[serde(default)]
+    last_fetched: Option<String>,
+    #[serde(default)]
+    error: Option<String>,
+    /// Ids of items the feed has listed, most recently listed first.
+    #[serde(default)]
+    seen: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AggregatedItem {
+    /// Stable id derived from the feed URL and the item's GUID or link.
+    pub id: String,
+    pub feed_url: String,
+    pub feed_title: String,
+    pub title: Option<String>,
+    pub link: Option<String>,
+    pub description: Option<String>,
+    pub pub_date: Option<String>,
+    pub author: Option<String>,
+    pub read: bool,
+    pub starred: bool,
+    /// When the item first appeared in its feed, RFC 3339.
+    pub first_seen: String,
+}
+
+impl AggregatedItem {
+    /// Published date for ordering, or when it was first seen if the feed gives none.
+    fn timestamp(&self) -> i64 {
+        self.pub_date
+            .as_deref()
+            .and_then(|d| DateTime::parse_from_rfc3339(d).ok())
+            .or_else(|| DateTime::parse_from_rfc3339(&self.first_seen).ok())
+            .map(|d| d.timestamp())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FeedStore {
+    #[serde(default)]
+    sources: HashMap<String, SourceState>,
+    #[serde(default)]
+    items: Vec<AggregatedItem>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedSource {
+    pub url: String,
+    /// The title set in settings, otherwise the feed's own.
+    pub title: String,
+    pub category: Option<String>,
+    pub last_fetched: Option<String>,
+    /// Why the last poll failed, if it did.
+    pub error: Option<String>,
+    pub unread: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedFailure {
+    pub url: String,
+    pub error: String,
+}
+
+/// Result of a poll, also the payload of the `feeds-updated` event.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FeedRefresh {
+    pub new_items: usize,
+    pub failed: Vec<FeedFailure>,
+}
+
+/// Location: `$DATA_DIR/DesQTA/profiles/<id>/feeds.json`
+fn store_file() -> PathBuf {
+    let mut dir = session::profile_dir();
+    dir.push("feeds.json");
+    dir
+}
+
+fn load_store() -> FeedStore {
+    std::fs::read_to_string(store_file())
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_store(store: &FeedStore) {
+    let path = store_file();
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    match serde_json::to_string(store) {
+        Ok(json) => {
+            if let Err(e) = std::fs::write(&path, json) {
+                eprintln!("[Desqta] Failed to save feeds: {}", e);
+            }
+        }
+        Err(e) => eprintln!("[Desqta] Failed to serialise feeds: {}", e),
+    }
+}
+
+/// Load, change and save the store while holding the lock.
+fn update_store<T>(f: impl FnOnce(&mut FeedStore) -> T) -> T {
+    let _guard = STORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut store = load_store();
+    let result = f(&mut store);
+    save_store(&store);
+    result
+}
+
+fn item_id(feed_url: &str, key: &str) -> String {
+    let hash = Sha256::digest(format!("{}\n{}", feed_url, key));
+    hash[..12].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+enum Fetched {
+    NotModified,
+    Feed {
+        feed: FeedResponse,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+}
+
+/// Fetch a feed, letting the server answer 304 if it has not changed since the last poll.
+async fn fetch(url: &str, state: &SourceState) -> Result<Fetched, NetError> {
+    let mut request = feeds::client().get(url);
+    if let Some(etag) = &state.etag {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = &state.last_modified {
+        request = request.header(IF_MODIFIED_SINCE, last_modified);
+    }
+    let resp = request.send().await?;
+    let status = resp.status();
+    if status == StatusCode::NOT_MODIFIED {
+        return Ok(Fetched::NotModified);
+    }
+    let header = |name| resp.headers().get(name).and_then(|v| v.to_str().ok()).map(|v| v.to_string());
+    let (etag, last_modified) = (header(ETAG), header(LAST_MODIFIED));
+    let content = resp.text().await?;
+    if !status.is_success() {
+        return Err(NetError::http(status.as_u16(), content));
+    }
+    let parsed = feeds::parse_feed(&content, url)?;
+    Ok(Fetched::Feed { feed: parsed.feed, etag, last_modified })
+}
+
+/// Add new items from `feed` and refresh the content of known ones, keeping their
+/// read and starred state. Items in `seen` that are no longer stored were pruned and stay
+/// out. Returns how many were new.
+fn merge(
+    items: &mut Vec<AggregatedItem>,
+    feed_url: &str,
+    feed: &FeedResponse,
+    seen: &mut Vec<String>,
+    now: &str,
+) -> usize {
+    let index: HashMap<String, usize> = items.iter().enumerate().map(|(i, item)| (item.id.clone(), i)).collect();
+    let previously_seen: HashSet<String> = seen.iter().cloned().collect();
+    let mut listed = Vec::new();
+    let mut added = 0;
+    for item in &feed.items {
+        let FeedItem { id, title, link, description, pub_date, author } = item.clone();
+        let Some(key) = id.clone().or_else(|| link.clone()).or_else(|| title.clone()) else { continue };
+        let id = item_id(feed_url, &key);
+        listed.push(id.clone());
+        match index.get(&id) {
+            Some(&i) => {
+                let existing = &mut items[i];
+                existing.feed_title = feed.title.clone();
+                existing.title = title;
+                existing.link = link;
+                existing.description = description;
+                existing.pub_date = pub_date;
+                existing.author = author;
+            }
+            None if previously_seen.contains(&id) => {}
+            None => {
+                items.push(AggregatedItem {
+                    id,
+                    feed_url: feed_url.to_string(),
+                    feed_title: feed.title.clone(),
+                    title,
+                    link,
+                    description,
+                    pub_date,
+                    author,
+                    read: false,
+                    starred: false,
+                    first_seen: now.to_string(),
+                });
+                added += 1;
+            }
+        }
+    }
+    let listed_now: HashSet<&String> = listed.iter().collect();
+    let older: Vec<String> = seen.drain(..).filter(|id| !listed_now.contains(id)).collect();
+    listed.extend(older);
+    listed.truncate(MAX_SEEN_PER_FEED);
+    *seen = listed;
+    added
+}
+
+/// Sort newest first and drop the oldest items of each feed beyond `MAX_ITEMS_PER_FEED`.
+fn prune(items: &mut Vec<AggregatedItem>) {
+    items.sort_by_key(|item| std::cmp::Reverse(item.timestamp()));
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    items.retain(|item| {
+        let count = counts.entry(item.feed_url.clone()).or_default();
+        *count += 1;
+        item.starred || *count <= MAX_ITEMS_PER_FEED
+    });
+}
+
+/// Poll every feed in settings concurrently and merge the results into the store.
+/// Feeds removed from settings are forgotten, apart from their starred items.
+pub(crate) async fn refresh() -> FeedRefresh {
+    let mut urls: Vec<String> = Settings::load().feeds.into_iter().map(|f| f.url.trim().to_string()).collect();
+    // The same feed may be listed twice with a different host case or trailing slash
+    let mut keys = HashSet::new();
+    urls.retain(|u| !u.is_empty() && keys.insert(opml::feed_key(u)));
+
+    let sources = {
+        let _guard = STORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        load_store().sources
+    };
+    let results = join_all(urls.iter().map(|url| {
+        let state = sources.get(url).cloned().unwrap_or_default();
+        async move { (url.clone(), fetch(url, &state).await) }
+    }))
+    .await;
+
+    let now = Utc::now().to_rfc3339();
+    update_store(|store| {
+        let mut summary = FeedRefresh::default();
+        for (url, result) in results {
+            let source = store.sources.entry(url.clone()).or_default();
+            source.last_fetched = Some(now.clone());
+            match result {
+                Ok(Fetched::NotModified) => source.error = None,
+                Ok(Fetched::Feed { feed, etag, last_modified }) => {
+                    source.title = feed.title.clone();
+                    source.etag = etag;
+                    source.last_modified = last_modified;
+                    source.error = None;
+                    summary.new_items += merge(&mut store.items, &url, &feed, &mut source.seen, &now);
+                }
+                Err(e) => {
+                    eprintln!("[Desqta] Failed to fetch feed {}: {}", url, e);
+                    source.error = Some(e.to_string());
+                    summary.failed.push(FeedFailure { url, error: e.to_string() });
+                }
+            }
+        }
+        store.sources.retain(|url, _| urls.contains(url));
+        store.items.retain(|item| item.starred || urls.contains(&item.feed_url));
+        prune(&mut store.items);
+        summary
+    })
+}
+
+async fn refresh_and_notify(app: &AppHandle) -> FeedRefresh {
+    let summary = refresh().await;
+    if summary.new_items > 0 {
+        println!("[Desqta] Feeds: {} new item(s)", summary.new_items);
+        let _ = app.emit("feeds-updated", &summary);
+    }
+    summary
+}
+
+/// Spawn the background poller, which refreshes the feeds every `feed_refresh_interval_mins`.
+pub fn start(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        sleep(STARTUP_DELAY).await;
+        loop {
+            let settings = Settings::load();
+            if !settings.feeds.is_empty() {
+                refresh_and_notify(&app).await;
+            }
+            sleep(Duration::from_secs(settings.feed_refresh_interval_mins.max(1) * 60)).await;
+        }
+    });
+}
+
+/// Items from every subscribed feed, newest first.
+#[tauri::command]
+pub fn get_aggregated_feed(limit: Option<usize>, unread_only: Option<bool>) -> Vec<AggregatedItem> {
+    let unread_only = unread_only.unwrap_or(false);
+    let mut items: Vec<AggregatedItem> = load_store()
+        .items
+        .into_iter()
+        .filter(|item| !unread_only || !item.read)
+        .collect();
+    items.sort_by_key(|item| std::cmp::Reverse(item.timestamp()));
+    items.truncate(limit.unwrap_or(usize::MAX));
+    items
+}
+
+/// Poll every feed now instead of waiting for the next scheduled refresh.
+#[tauri::command]
+pub async fn refresh_feeds(app: AppHandle) -> FeedRefresh {
+    refresh_and_notify(&app).await
+}
+
+/// Subscribed feeds with their titles, poll status and unread counts.
+#[tauri::command]
+pub fn get_feed_sources() -> Vec<FeedSource> {
+    let store = load_store();
+    Settings::load()
+        .feeds
+        .into_iter()
+        .map(|feed| {
+            let state = store.sources.get(&feed.url).cloned().unwrap_or_default();
+            FeedSource {
+                unread: store.items.iter().filter(|i| i.feed_url == feed.url && !i.read).count(),
+                url: feed.url,
+                title: feed.title.filter(|t| !t.is_empty()).unwrap_or(state.title),
+                category: feed.category,
+                last_fetched: state.last_fetched,
+                error: state.error,
+            }
+        })
+        .collect()
+}
+
+fn set_flag(id: &str, f: impl FnOnce(&mut AggregatedItem)) -> Result<(), NetError> {
+    update_store(|store| match store.items.iter_mut().find(|item| item.id == id) {
+        Some(item) => {
+            f(item);
+            Ok(())
+        }
+        None => Err(NetError::NotFound { message: format!("No feed item with id {}", id) }),
+    })
+}
+
+#[tauri::command]
+pub fn set_feed_item_read(id: String, read: bool) -> Result<(), NetError> {
+    set_flag(&id, |item| item.read = read)
+}
+
+#[tauri::command]
+pub fn set_feed_item_starred(id: String, starred: bool) -> Result<(), NetError> {
+    set_flag(&id, |item| item.starred = starred)
+}
+
+/// Mark every item read, or only those from `feed_url`. Returns how many changed.
+#[tauri::command]
+pub fn mark_all_feed_items_read(feed_url: Option<String>) -> usize {
+    update_store(|store| {
+        let mut changed = 0;
+        for item in store.items.iter_mut() {
+            if !item.read && feed_url.as_ref().is_none_or(|url| &item.feed_url == url) {
+                item.read = true;
+                changed += 1;
+            }
+        }
+        changed
+    })
+}
@@ -0,0 +1,487 @@
+use ammonia::{Builder, UrlRelative};
+use anyhow::{anyhow, Result};
+use chrono::DateTime;
+use reqwest::Client;
+use rss::Channel;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::io::Cursor;
+use std::sync::OnceLock;
+use std::time::Duration;
+use url::Url;
+use xmltree::{Element, EmitterConfig, XMLNode};
+
+use crate::neterror::NetError;
+
+/// Some school and news sites refuse requests that do not look like a browser.
+const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/114.0.0.0 Safari/537.36";
+
+static FEED_CLIENT: OnceLock<Client> = OnceLock::new();
+
+/// Client for fetching feeds. Kept apart from netgrab's so feed sites never see SEQTA headers.
+pub(crate) fn client() -> &'static Client {
+    FEED_CLIENT.get_or_init(|| {
+        Client::builder()
+            .user_agent(USER_AGENT)
+            .connect_timeout(Duration::from_secs(15))
+            .timeout(Duration::from_secs(30))
+            .build()
+            .expect("Failed to create feed HTTP client")
+    })
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedFormat {
+    /// RSS 0.9x, 1.0 (RDF) or 2.0.
+    Rss,
+    Atom,
+    JsonFeed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedItem {
+    /// GUID, Atom id or JSON Feed id, falling back to the link.
+    pub id: Option<String>,
+    pub title: Option<String>,
+    pub link: Option<String>,
+    /// HTML summary or content.
+    pub description: Option<String>,
+    /// RFC 3339 when the feed's date could be parsed, otherwise as given.
+    pub pub_date: Option<String>,
+    pub author: Option<String>,
+}
+
+/// A feed in any supported format, normalised.
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedResponse {
+    pub title: String,
+    pub link: Option<String>,
+    pub description: Option<String>,
+    pub format: FeedFormat,
+    pub items: Vec<FeedItem>,
+}
+
+fn non_empty(s: &str) -> Option<String> {
+    let s = s.trim();
+    (!s.is_empty()).then(|| s.to_string())
+}
+
+/// RSS uses RFC 2822 dates, Atom and JSON Feed RFC 3339.
+fn normalise_date(raw: &str) -> Option<String> {
+    let raw = raw.trim();
+    DateTime::parse_from_rfc2822(raw)
+        .or_else(|_| DateTime::parse_from_rfc3339(raw))
+        .map(|d| d.to_rfc3339())
+        .ok()
+        .or_else(|| non_empty(raw))
+}
+
+fn from_rss(channel: &Channel) -> FeedResponse {
+    let items = channel
+        .items()
+        .iter()
+        .map(|item| {
+            let link = item.link().and_then(non_empty);
+            // RSS 1.0 has no pubDate; it uses Dublin Core's dc:date instead
+            let date = item
+                .pub_date()
+                .or_else(|| item.dublin_core_ext().and_then(|dc| dc.dates().first().map(|d| d.as_str())));
+            FeedItem {
+                id: item.guid().and_then(|g| non_empty(g.value())).or_else(|| link.clone()),
+                title: item.title().and_then(non_empty),
+                link,
+                description: item.description().or(item.content()).and_then(non_empty),
+                pub_date: date.and_then(normalise_date),
+                author: item
+                    .author()
+                    .or_else(|| item.dublin_core_ext().and_then(|dc| dc.creators().first().map(|c| c.as_str())))
+                    .and_then(non_empty),
+            }
+        })
+        .collect();
+    FeedResponse {
+        title: channel.title().trim().to_string(),
+        link: non_empty(channel.link()),
+        description: non_empty(channel.description()),
+        format: FeedFormat::Rss,
+        items,
+    }
+}
+
+/// Text of an Atom text construct; `type="xhtml"` content is markup, not text.
+fn atom_text(elem: &Element) -> Option<String> {
+    if elem.attributes.get("type").map(String::as_str) == Some("xhtml") {
+        let mut out = Vec::new();
+        for child in &elem.children {
+            if let XMLNode::Element(e) = child {
+                let _ = e.write_with_config(&mut out, EmitterConfig::new().write_document_declaration(false));
+            }
+        }
+        return non_empty(&String::from_utf8_lossy(&out));
+    }
+    elem.get_text().and_then(|t| non_empty(&t))
+}
+
+/// The `rel="alternate"` link, or the first link without a `rel`.
+fn atom_link(elem: &Element) -> Option<String> {
+    let links: Vec<&Element> = elem
+        .children
+        .iter()
+        .filter_map(|c| match c {
+            XMLNode::Element(e) if e.name == "link" => Some(e),
+            _ => None,
+        })
+        .collect();
+    links
+        .iter()
+        .find(|l| l.attributes.get("rel").is_none_or(|rel| rel == "alternate"))
+        .or(links.first())
+        .and_then(|l| l.attributes.get("href"))
+        .and_then(|href| non_empty(href))
+}
+
+fn from_atom(root: &Element) -> FeedResponse {
+    let text = |elem: &Element, name: &str| elem.get_child(name).and_then(atom_text);
+    let items = root
+        .children
+        .iter()
+        .filter_map(|c| match c {
+            XMLNode::Element(e) if e.name == "entry" => Some(e),
+            _ => None,
+        })
+        .map(|entry| {
+            let link = atom_link(entry);
+            FeedItem {
+                id: text(entry, "id").or_else(|| link.clone()),
+                title: text(entry, "title"),
+                link,
+                description: text(entry, "summary").or_else(|| text(entry, "content")),
+                pub_date: text(entry, "published").or_else(|| text(entry, "updated")).and_then(|d| normalise_date(&d)),
+                author: entry.get_child("author").and_then(|a| text(a, "name")),
+            }
+        })
+        .collect();
+    FeedResponse {
+        title: text(root, "title").unwrap_or_default(),
+        link: atom_link(root),
+        description: text(root, "subtitle"),
+        format: FeedFormat::Atom,
+        items,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonFeedAuthor {
+    #[serde(default)]
+    name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonFeedItem {
+    #[serde(default, deserialize_with = "any_id")]
+    id: Option<String>,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    external_url: Option<String>,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    content_html: Option<String>,
+    #[serde(default)]
+    content_text: Option<String>,
+    #[serde(default)]
+    summary: Option<String>,
+    #[serde(default)]
+    date_published: Option<String>,
+    #[serde(default)]
+    date_modified: Option<String>,
+    /// JSON Feed 1.1
+    #[serde(default)]
+    authors: Vec<JsonFeedAuthor>,
+    /// JSON Feed 1.0
+    #[serde(default)]
+    author: Option<JsonFeedAuthor>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonFeed {
+    version: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    home_page_url: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    items: Vec<JsonFeedItem>,
+}
+
+/// The spec says ids are strings, but plenty of feeds use numbers.
+fn any_id<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::String(s) => non_empty(&s),
+        Value::Null => None,
+        other => Some(other.to_string()),
+    })
+}
+
+fn from_json_feed(feed: JsonFeed) -> FeedResponse {
+    let items = feed
+        .items
+        .into_iter()
+        .map(|item| {
+            let link = item.url.or(item.external_url).and_then(|u| non_empty(&u));
+            FeedItem {
+                id: item.id.or_else(|| link.clone()),
+                title: item.title.and_then(|t| non_empty(&t)),
+                link,
+                description: item.content_html.or(item.summary).or(item.content_text).and_then(|d| non_empty(&d)),
+                pub_date: item.date_published.or(item.date_modified).and_then(|d| normalise_date(&d)),
+                author: item.authors.into_iter().next().or(item.author).and_then(|a| a.name),
+            }
+        })
+        .collect();
+    FeedResponse {
+        title: feed.title.trim().to_string(),
+        link: feed.home_page_url.and_then(|u| non_empty(&u)),
+        description: feed.description.and_then(|d| non_empty(&d)),
+        format: FeedFormat::JsonFeed,
+        items,
+    }
+}
+
+/// Allowlist cleaner for HTML from feeds and articles. Scripts, styles, event handlers
+/// and non-web URLs are removed; relative URLs are resolved against `base`, or dropped
+/// when there is none.
+pub(crate) fn sanitiser(base: Option<&Url>) -> Builder<'static> {
+    let mut builder = Builder::default();
+    builder
+        .url_schemes(HashSet::from(["http", "https", "mailto"]))
+        .link_rel(Some("noopener noreferrer nofollow"))
+        .url_relative(match base {
+            Some(base) => UrlRelative::RewriteWithBase(base.clone()),
+            None => UrlRelative::Deny,
+        });
+    builder
+}
+
+fn clean_html(html: &str, base: Option<&Url>) -> Option<String> {
+    non_empty(&sanitiser(base).clean(html).to_string())
+}
+
+/// `link` resolved against `base` as an absolute http(s) URL. Anything else, such as a
+/// `javascript:` link, gives `None`.
+pub(crate) fn resolve_link(base: Option<&Url>, link: &str) -> Option<String> {
+    let link = link.trim();
+    let url = match base {
+        Some(base) => base.join(link),
+        None => Url::parse(link),
+    }
+    .ok()?;
+    matches!(url.scheme(), "http" | "https").then(|| url.to_string())
+}
+
+/// An item's resolved link, and the base for its content. Images in a post are usually
+/// relative to the post itself, so that is preferred over the feed URL.
+fn item_base(feed_url: Option<&Url>, link: Option<&str>) -> (Option<String>, Option<Url>) {
+    let link = link.and_then(|l| resolve_link(feed_url, l));
+    let base = link.as_deref().and_then(|l| Url::parse(l).ok()).or_else(|| feed_url.cloned());
+    (link, base)
+}
+
+/// Clean the RSS channel in place, so both the normalised items and the `channel_to_json`
+/// output see the sanitised content.
+fn sanitise_channel(channel: &mut Channel, feed_url: Option<&Url>) {
+    let link = resolve_link(feed_url, channel.link()).unwrap_or_default();
+    channel.set_link(link);
+    for item in channel.items_mut() {
+        let (link, base) = item_base(feed_url, item.link());
+        let description = item.description().and_then(|d| clean_html(d, base.as_ref()));
+        let content = item.content().and_then(|c| clean_html(c, base.as_ref()));
+        item.set_link(link);
+        item.set_description(description);
+        item.set_content(content);
+    }
+}
+
+fn sanitise_feed(feed: &mut FeedResponse, feed_url: Option<&Url>) {
+    feed.link = feed.link.take().and_then(|l| resolve_link(feed_url, &l));
+    for item in &mut feed.items {
+        let (link, base) = item_base(feed_url, item.link.as_deref());
+        item.link = link;
+        item.description = item.description.take().and_then(|d| clean_html(&d, base.as_ref()));
+    }
+}
+
+/// A parsed feed, with the raw RSS channel kept for `channel_to_json`.
+pub(crate) struct ParsedFeed {
+    pub feed: FeedResponse,
+    channel: Option<Channel>,
+}
+
+/// Detect the feed's format from its content and parse it. Item HTML is sanitised, and
+/// relative links and images are resolved against `feed_url`.
+pub(crate) fn parse_feed(content: &str, feed_url: &str) -> Result<ParsedFeed, NetError> {
+    let content = content.trim_start_matches('\u{feff}').trim_start();
+    let base = Url::parse(feed_url).ok();
+
+    if content.starts_with('{') {
+        let feed: JsonFeed = serde_json::from_str(content)
+            .map_err(|e| NetError::decode(format!("Failed to parse JSON Feed: {}", e)))?;
+        if !feed.version.contains("jsonfeed.org") {
+            return Err(NetError::decode(format!("Unsupported JSON feed version {}", feed.version)));
+        }
+        let mut feed = from_json_feed(feed);
+        sanitise_feed(&mut feed, base.as_ref());
+        return Ok(ParsedFeed { feed, channel: None });
+    }
+
+    let root = Element::parse(Cursor::new(content))
+        .map_err(|e| NetError::decode(format!("Failed to parse feed: {}", e)))?;
+    match root.name.as_str() {
+        "feed" => {
+            let mut feed = from_atom(&root);
+            sanitise_feed(&mut feed, base.as_ref());
+            Ok(ParsedFeed { feed, channel: None })
+        }
+        "rss" | "RDF" => {
+            let mut channel = Channel::read_from(content.as_bytes())
+                .map_err(|e| NetError::decode(format!("Failed to parse RSS feed: {}", e)))?;
+            sanitise_channel(&mut channel, base.as_ref());
+            Ok(ParsedFeed { feed: from_rss(&channel), channel: Some(channel) })
+        }
+        other => Err(NetError::decode(format!("Unrecognised feed format <{}>", other))),
+    }
+}
+
+/// JSON for the frontend: the normalised `FeedResponse` fields (`title`, `items`, ...)
+/// alongside the older `channel`/`feeds` shape, which is built from the RSS XML when
+/// there is one and approximated for Atom and JSON Feed.
+pub(crate) fn feed_to_json(parsed: &ParsedFeed) -> Result<Value> {
+    let mut json = match &parsed.channel {
+        Some(channel) => channel_to_json(channel)?,
+        None => {
+            let feed = &parsed.feed;
+            json!({
+                "channel": {
+                    "title": feed.title,
+                    "link": feed.link,
+                    "description": feed.description,
+                },
+                "feeds": feed.items.iter().map(|item| json!({
+                    "title": item.title,
+                    "link": item.link,
+                    "description": item.description,
+                    "pubDate": item.pub_date,
+                    "guid": item.id,
+                    "author": item.author,
+                })).collect::<Vec<_>>(),
+            })
+        }
+    };
+    if let (Value::Object(map), Value::Object(normalised)) = (&mut json, serde_json::to_value(&parsed.feed)?) {
+        map.extend(normalised);
+    }
+    Ok(json)
+}
+
+/// Fetch a news feed in RSS, Atom or JSON Feed format.
+#[tauri::command]
+pub async fn get_rss_feed(feed: &str) -> Result<Value, NetError> {
+    let res = client()
+        .get(feed)
+        .send()
+        .await?;
+
+    let status = res.status();
+    let content = res
+        .text()
+        .await?;
+
+    if !status.is_success() {
+        return Err(NetError::http(status.as_u16(), content));
+    }
+
+    let parsed = parse_feed(&content, feed)?;
+    let json = feed_to_json(&parsed)
+        .map_err(|e| NetError::decode(format!("Failed to convert to JSON: {}", e)))?;
+
+    Ok(json)
+}
+pub fn channel_to_json(channel: &Channel) -> Result<Value> {
+    fn xml_to_json(elem: &Element) -> Value {
+        let text = elem.get_text();
+        let has_text = text.as_ref().map(|t| !t.trim().is_empty()).unwrap_or(false);
+
+        let has_attrs = !elem.attributes.is_empty();
+        let has_children = elem.children.iter().any(|c| matches!(c, XMLNode::Element(_)));
+
+        if !has_attrs && !has_children && has_text {
+            return Value::String(text.unwrap().to_string());
+        }
+
+        let mut map = serde_json::Map::new();
+
+        if has_attrs {
+            map.insert("@attributes".into(), json!(elem.attributes));
+        }
+
+        for child in &elem.children {
+            if let XMLNode::Element(child_elem) = child {
+                let child_json = xml_to_json(child_elem);
+                map.entry(child_elem.name.clone())
+                    .and_modify(|v| {
+                        if let Value::Array(arr) = v {
+                            arr.push(child_json.clone());
+                        } else {
+                            *v = Value::Array(vec![v.take(), child_json.clone()]);
+                        }
+                    })
+                    .or_insert(child_json);
+            }
+        }
+
+        if has_text {
+            map.insert("text".into(), Value::String(text.unwrap().to_string()));
+        }
+
+        Value::Object(map)
+    }
+
+    let xml_str = channel.to_string();
+    let root = Element::parse(Cursor::new(xml_str))
+        .map_err(|e| anyhow!("Failed to parse XML: {}", e))?;
+
+    let mut root_json = xml_to_json(&root);
+
+    // Parse item elements into feeds array using flexible xml_to_json
+    let feeds: Vec<Value> = root
+        .get_child("channel")
+        .map(|channel_elem| {
+            channel_elem
+                .children
+                .iter()
+                .filter_map(|node| {
+                    if let XMLNode::Element(child) = node {
+                        if child.name == "item" {
+                            Some(xml_to_json(child))
+                        } else {
+                            None
+                        }
+                    } else {
+                        None
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if let Value::Object(ref mut map) = root_json {
+        map.insert("feeds".to_string(), Value::Array(feeds));
+    }
+
+    Ok(root_json)
+}
@@ -0,0 +1,78 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::{AppHandle, Emitter};
+use tokio::time::{sleep, Duration};
+
+use crate::netgrab;
+use crate::neterror::NetError;
+use crate::session;
+use crate::settings::Settings;
+
+/// Shortest interval we will ever ping SEQTA at, whatever the settings say.
+const MIN_INTERVAL_SECS: u64 = 30;
+
+static PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// Payload of the `heartbeat-failed` event.
+#[derive(Debug, Clone, Serialize)]
+struct HeartbeatFailure {
+    error: NetError,
+    consecutive_failures: u32,
+}
+
+/// Spawn the background task that keeps the SEQTA session alive by posting to
+/// `/seqta/student/heartbeat`, including while the window is hidden to the tray.
+pub fn start(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut consecutive_failures = 0;
+        loop {
+            let interval = Settings::load().heartbeat_interval_secs;
+            // An interval of 0 disables the heartbeat; check again later in case it is turned back on
+            sleep(Duration::from_secs(interval.max(MIN_INTERVAL_SECS))).await;
+            if interval == 0 || PAUSED.load(Ordering::Relaxed) || !session::Session::exists() {
+                continue;
+            }
+
+            let error = match netgrab::validate_session().await {
+                Ok(status) if status.valid => None,
+                // netgrab has already emitted `session-expired` for this case
+                Ok(_) => Some(NetError::SessionExpired),
+                Err(e) => Some(e),
+            };
+
+            match error {
+                None => {
+                    if consecutive_failures > 0 {
+                        println!("[Desqta] Heartbeat recovered after {} failures", consecutive_failures);
+                        let _ = app.emit("heartbeat-recovered", ());
+                    }
+                    consecutive_failures = 0;
+                }
+                Some(error) => {
+                    consecutive_failures += 1;
+                    eprintln!("[Desqta] Heartbeat failed: {}", error);
+                    let payload = HeartbeatFailure { error, consecutive_failures };
+                    if let Err(e) = app.emit("heartbeat-failed", payload) {
+                        eprintln!("[Desqta] Failed to emit heartbeat-failed: {}", e);
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Stop sending heartbeats until `resume_heartbeat` is called.
+#[tauri::command]
+pub fn pause_heartbeat() {
+    PAUSED.store(true, Ordering::Relaxed);
+}
+
+#[tauri::command]
+pub fn resume_heartbeat() {
+    PAUSED.store(false, Ordering::Relaxed);
+}
+
+#[tauri::command]
+pub fn is_heartbeat_paused() -> bool {
+    PAUSED.load(Ordering::Relaxed)
+}
@@ -0,0 +1,232 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use tauri_plugin_dialog::DialogExt;
+use tokio::sync::oneshot;
+use url::Url;
+
+use crate::db;
+use crate::neterror::NetError;
+use crate::seqta::{self, Assessment, TimetableLesson};
+use crate::session;
+
+const PRODID: &str = "-//DesQTA//SEQTA Timetable//EN";
+
+/// Escape a TEXT value (RFC 5545 3.3.11).
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Fold a content line to 75 octets, without splitting a UTF-8 character (RFC 5545 3.1).
+fn fold(line: &str) -> String {
+    let mut out = String::with_capacity(line.len() + line.len() / 74 * 3);
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
+
+/// The school's host, so UIDs from two SEQTA profiles never collide.
+fn uid_domain() -> String {
+    Url::parse(&session::Session::load().base_url)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_string()))
+        .unwrap_or_else(|| "desqta".to_string())
+}
+
+fn parse_date(date: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(date.get(..10)?, "%Y-%m-%d").ok()
+}
+
+fn parse_time(time: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(time, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M"))
+        .ok()
+}
+
+/// Local "floating" time; lessons happen at the school's wall-clock time wherever the
+/// calendar is viewed from.
+fn format_local(dt: NaiveDateTime) -> String {
+    dt.format("%Y%m%dT%H%M%S").to_string()
+}
+
+fn lesson_event(lesson: &TimetableLesson, domain: &str, stamp: &str) -> Option<String> {
+    let date = parse_date(&lesson.date)?;
+    let start = date.and_time(parse_time(&lesson.from)?);
+    let end = date.and_time(parse_time(&lesson.until)?);
+
+    // Keyed on the class and slot rather than the content, so a room change updates the event
+    let slot = lesson.period.clone().unwrap_or_else(|| lesson.from.clone());
+    let class = lesson.metaclass.map(|m| m.to_string()).unwrap_or_else(|| lesson.code.clone());
+    let uid = format!("lesson-{}-{}-{}@{}", lesson.date, slot, class, domain);
+
+    let mut lines = vec![
+        "BEGIN:VEVENT".to_string(),
+        format!("UID:{}", escape(&uid)),
+        format!("DTSTAMP:{}", stamp),
+        format!("DTSTART:{}", format_local(start)),
+        format!("DTEND:{}", format_local(end)),
+        format!("SUMMARY:{}", escape(if lesson.description.is_empty() { &lesson.code } else { &lesson.description })),
+    ];
+    if let Some(room) = lesson.room.as_deref().filter(|r| !r.is_empty()) {
+        lines.push(format!("LOCATION:{}", escape(room)));
+    }
+    let mut details = vec![format!("Class: {}", lesson.code)];
+    if let Some(staff) = lesson.staff.as_deref().filter(|s| !s.is_empty()) {
+        details.push(format!("Teacher: {}", staff));
+    }
+    if let Some(period) = lesson.period.as_deref().filter(|p| !p.is_empty()) {
+        details.push(format!("Period: {}", period));
+    }
+    lines.push(format!("DESCRIPTION:{}", escape(&details.join("\n"))));
+    lines.push("CATEGORIES:Lesson".to_string());
+    lines.push("END:VEVENT".to_string());
+    Some(lines.iter().map(|l| fold(l)).collect())
+}
+
+/// Assessments become all-day events on their due date.
+fn assessment_event(assessment: &Assessment, domain: &str, stamp: &str) -> Option<String> {
+    let due = parse_date(&assessment.due)?;
+    let uid = format!("assessment-{}@{}", assessment.id, domain);
+    let mut summary = format!("Due: {}", assessment.title);
+    if !assessment.subject.is_empty() {
+        summary = format!("{} ({})", summary, assessment.subject);
+    }
+
+    let lines = [
+        "BEGIN:VEVENT".to_string(),
+        format!("UID:{}", escape(&uid)),
+        format!("DTSTAMP:{}", stamp),
+        format!("DTSTART;VALUE=DATE:{}", due.format("%Y%m%d")),
+        format!("DTEND;VALUE=DATE:{}", (due + Duration::days(1)).format("%Y%m%d")),
+        format!("SUMMARY:{}", escape(&summary)),
+        format!("DESCRIPTION:{}", escape(&format!("Class: {}", assessment.code))),
+        "CATEGORIES:Assessment".to_string(),
+        "TRANSP:TRANSPARENT".to_string(),
+        "END:VEVENT".to_string(),
+    ];
+    Some(lines.iter().map(|l| fold(l)).collect())
+}
+
+/// Build an RFC 5545 calendar. UIDs depend only on the SEQTA data, so re-exporting
+/// updates events in calendar clients instead of duplicating them.
+pub(crate) fn build_calendar(name: &str, lessons: &[TimetableLesson], assessments: &[Assessment]) -> String {
+    let domain = uid_domain();
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+    let mut out = String::new();
+    for line in [
+        "BEGIN:VCALENDAR",
+        "VERSION:2.0",
+        &format!("PRODID:{}", PRODID),
+        "CALSCALE:GREGORIAN",
+        "METHOD:PUBLISH",
+        &format!("X-WR-CALNAME:{}", escape(name)),
+    ] {
+        out.push_str(&fold(line));
+    }
+    for lesson in lessons {
+        out.extend(lesson_event(lesson, &domain, &stamp));
+    }
+    for assessment in assessments {
+        out.extend(assessment_event(assessment, &domain, &stamp));
+    }
+    out.push_str(&fold("END:VCALENDAR"));
+    out
+}
+
+/// Upcoming assessments plus any stored offline, limited to those due within `from..=until`.
+pub(crate) async fn assessments_between(from: &str, until: &str) -> Result<Vec<Assessment>, NetError> {
+    let mut assessments = seqta::upcoming_assessments(false).await?;
+    for stored in db::get_offline_assessments(false).unwrap_or_default() {
+        if !assessments.iter().any(|a| a.id == stored.id) {
+            assessments.push(stored);
+        }
+    }
+    let (from, until) = (parse_date(from), parse_date(until));
+    assessments.retain(|a| {
+        parse_date(&a.due).is_some_and(|due| from.is_none_or(|f| due >= f) && until.is_none_or(|u| due <= u))
+    });
+    Ok(assessments)
+}
+
+/// Export lessons and assessment due dates between `from` and `until` (`YYYY-MM-DD`) to
+/// an .ics file chosen by the user. Returns the saved path, or `None` if cancelled.
+#[tauri::command]
+pub async fn export_calendar(
+    app: tauri::AppHandle,
+    from: String,
+    until: String,
+    include_lessons: Option<bool>,
+    include_assessments: Option<bool>,
+) -> Result<Option<String>, NetError> {
+    let lessons = if include_lessons.unwrap_or(true) {
+        seqta::timetable(&from, &until, false).await?
+    } else {
+        Vec::new()
+    };
+    let assessments = if include_assessments.unwrap_or(true) {
+        assessments_between(&from, &until).await?
+    } else {
+        Vec::new()
+    };
+    let calendar = build_calendar("SEQTA Timetable", &lessons, &assessments);
+
+    // The blocking dialog would tie up an async runtime worker until the user answers
+    let (tx, rx) = oneshot::channel();
+    app.dialog()
+        .file()
+        .add_filter("iCalendar", &["ics"])
+        .set_file_name("seqta-timetable.ics")
+        .save_file(move |path| {
+            let _ = tx.send(path);
+        });
+    let Some(path) = rx.await.ok().flatten() else {
+        return Ok(None);
+    };
+    let path = path.into_path().map_err(|e| NetError::Io { message: e.to_string() })?;
+    std::fs::write(&path, calendar)?;
+    println!("[Desqta] Exported {} lessons and {} assessments to {}", lessons.len(), assessments.len(), path.display());
+    Ok(Some(path.to_string_lossy().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_text_values() {
+        assert_eq!(escape("Maths; Room 4, B\\C"), "Maths\\; Room 4\\, B\\\\C");
+        assert_eq!(escape("line one\r\nline two\nthree"), "line one\\nline two\\nthree");
+    }
+
+    #[test]
+    fn folds_long_lines_at_75_octets() {
+        assert_eq!(fold("SUMMARY:Maths"), "SUMMARY:Maths\r\n");
+
+        let folded = fold(&"a".repeat(160));
+        let lines: Vec<&str> = folded.trim_end_matches("\r\n").split("\r\n").collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|l| l.len() <= 75));
+        assert!(lines[1..].iter().all(|l| l.starts_with(' ')));
+        assert_eq!(lines.concat().replace(' ', ""), "a".repeat(160));
+    }
+
+    #[test]
+    fn never_splits_a_character_when_folding() {
+        let folded = fold(&format!("SUMMARY:{}", "é".repeat(50)));
+        for line in folded.split("\r\n") {
+            assert!(line.len() <= 75);
+        }
+        assert_eq!(folded.replace("\r\n ", "").trim_end(), format!("SUMMARY:{}", "é".repeat(50)));
+    }
+}
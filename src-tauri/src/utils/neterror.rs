@@ -0,0 +1,176 @@
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::fmt;
+
+/// Error returned by every network-facing command.
+///
+/// Serialised as `{ "code": "...", "message": "...", ...details }` so the frontend can
+/// switch on `code` instead of matching error strings.
+#[derive(Debug, Clone)]
+pub enum NetError {
+    /// The server could not be reached (offline, DNS failure, connection refused).
+    Network { message: String, attempts: u32 },
+    /// The server took too long to respond.
+    Timeout { message: String, attempts: u32 },
+    /// The server answered with a non-success status.
+    Http { status: u16, body: String, attempts: u32 },
+    /// SEQTA no longer accepts the saved session and the user must log in again.
+    SessionExpired,
+    /// No credentials are available for the service being called.
+    NotAuthenticated { message: String },
+    /// The requested resource does not exist.
+    NotFound { message: String },
+    InvalidUrl { message: String },
+    Io { message: String },
+    /// The response could not be read or parsed.
+    Decode { message: String },
+    /// A file was refused before it was sent, e.g. too large or of a type SEQTA does not accept.
+    Rejected { message: String },
+    /// The user cancelled the transfer.
+    Cancelled,
+}
+
+impl NetError {
+    /// Stable identifier for the error kind, shared with the TypeScript side.
+    pub fn code(&self) -> &'static str {
+        match self {
+            NetError::Network { .. } => "network",
+            NetError::Timeout { .. } => "timeout",
+            NetError::Http { .. } => "http",
+            NetError::SessionExpired => "session_expired",
+            NetError::NotAuthenticated { .. } => "not_authenticated",
+            NetError::NotFound { .. } => "not_found",
+            NetError::InvalidUrl { .. } => "invalid_url",
+            NetError::Io { .. } => "io",
+            NetError::Decode { .. } => "decode",
+            NetError::Rejected { .. } => "rejected",
+            NetError::Cancelled => "cancelled",
+        }
+    }
+
+    /// Record how many attempts were made before giving up.
+    pub fn with_attempts(mut self, count: u32) -> Self {
+        match &mut self {
+            NetError::Network { attempts, .. }
+            | NetError::Timeout { attempts, .. }
+            | NetError::Http { attempts, .. } => *attempts = count,
+            _ => {}
+        }
+        self
+    }
+
+    pub fn attempts(&self) -> u32 {
+        match self {
+            NetError::Network { attempts, .. }
+            | NetError::Timeout { attempts, .. }
+            | NetError::Http { attempts, .. } => *attempts,
+            _ => 1,
+        }
+    }
+
+    /// True if the request never got an answer from the server at all, so a cached copy
+    /// is a reasonable substitute and background work should wait before trying again.
+    /// A server error is an answer, and is reported as is.
+    pub fn is_unreachable(&self) -> bool {
+        matches!(self, NetError::Network { .. } | NetError::Timeout { .. })
+    }
+
+    pub fn decode(message: impl Into<String>) -> Self {
+        NetError::Decode { message: message.into() }
+    }
+
+    pub fn http(status: u16, body: impl Into<String>) -> Self {
+        NetError::Http { status, body: body.into(), attempts: 1 }
+    }
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetError::Network { message, .. } => write!(f, "Network error: {}", message)?,
+            NetError::Timeout { message, .. } => write!(f, "Request timed out: {}", message)?,
+            NetError::Http { status, body, .. } => {
+                write!(f, "Server returned {}", status)?;
+                let body = body.trim();
+                if !body.is_empty() {
+                    // Error pages can be entire HTML documents, so only show the start
+                    let preview: String = body.chars().take(200).collect();
+                    write!(f, ": {}", preview)?;
+                    if body.chars().count() > 200 {
+                        write!(f, "...")?;
+                    }
+                }
+            }
+            NetError::SessionExpired => write!(f, "Your SEQTA session has expired. Please log in again.")?,
+            NetError::NotAuthenticated { message } => write!(f, "{}", message)?,
+            NetError::NotFound { message } => write!(f, "{}", message)?,
+            NetError::InvalidUrl { message } => write!(f, "Invalid URL: {}", message)?,
+            NetError::Io { message } => write!(f, "File error: {}", message)?,
+            NetError::Decode { message } => write!(f, "Failed to read response: {}", message)?,
+            NetError::Rejected { message } => write!(f, "{}", message)?,
+            NetError::Cancelled => write!(f, "Cancelled")?,
+        }
+
+        let attempts = self.attempts();
+        if attempts > 1 {
+            write!(f, " (gave up after {} tries)", attempts)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for NetError {}
+
+impl Serialize for NetError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("code", self.code())?;
+        map.serialize_entry("message", &self.to_string())?;
+        match self {
+            NetError::Network { attempts, .. } | NetError::Timeout { attempts, .. } => {
+                map.serialize_entry("attempts", attempts)?;
+            }
+            NetError::Http { status, body, attempts } => {
+                map.serialize_entry("status", status)?;
+                map.serialize_entry("body", body)?;
+                map.serialize_entry("attempts", attempts)?;
+            }
+            _ => {}
+        }
+        map.end()
+    }
+}
+
+impl From<reqwest::Error> for NetError {
+    fn from(e: reqwest::Error) -> Self {
+        let message = e.to_string();
+        if e.is_timeout() {
+            NetError::Timeout { message, attempts: 1 }
+        } else if e.is_builder() {
+            NetError::InvalidUrl { message }
+        } else if e.is_decode() || e.is_body() {
+            NetError::Decode { message }
+        } else if let Some(status) = e.status() {
+            NetError::Http { status: status.as_u16(), body: message, attempts: 1 }
+        } else {
+            NetError::Network { message, attempts: 1 }
+        }
+    }
+}
+
+impl From<std::io::Error> for NetError {
+    fn from(e: std::io::Error) -> Self {
+        NetError::Io { message: e.to_string() }
+    }
+}
+
+impl From<serde_json::Error> for NetError {
+    fn from(e: serde_json::Error) -> Self {
+        NetError::Decode { message: e.to_string() }
+    }
+}
+
+impl From<url::ParseError> for NetError {
+    fn from(e: url::ParseError) -> Self {
+        NetError::InvalidUrl { message: e.to_string() }
+    }
+}
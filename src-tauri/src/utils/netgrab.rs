@@ -1,44 +1,41 @@
 use reqwest::{self, RequestBuilder};
-use reqwest::Client;
-use rss::Channel;
 use serde::{Deserialize, Serialize};
-use xmltree::{Element, XMLNode};
 use serde_json::{json, Value};
-use std::{io::Cursor, sync::OnceLock, fs};
+use std::{sync::{Mutex, OnceLock}, fs, path::PathBuf};
 use std::collections::HashMap;
-use anyhow::{Result, anyhow};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use rand::Rng;
+use tokio::time::sleep;
 use url::Url;
-use url::form_urlencoded;
 
 use base64::{engine::general_purpose, Engine as _};
+use sha2::{Digest, Sha256};
 // opens a file using the default program:
 
+use crate::db;
+use crate::neterror::NetError;
+use crate::search;
 use crate::session;
+use crate::settings::Settings;
 
 static GLOBAL_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct HomeworkItem {
-    pub meta: i32,
-    pub id: i32,
-    pub title: String,
-    pub items: Vec<String>,
-}
+/// The retry policy from settings and when it was read, so requests do not each load
+/// and parse `settings.json`.
+static RETRY_POLICY: Mutex<Option<(Instant, RetryPolicy)>> = Mutex::new(None);
+/// How long a retry policy read from settings is reused.
+const RETRY_POLICY_TTL: Duration = Duration::from_secs(30);
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct HomeworkResponse {
-    pub payload: Vec<HomeworkItem>,
-    pub status: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
+// Variant names are what the frontend sends
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub enum RequestMethod {
     GET,
     POST,
 }
 
 /// Build an HTTP client with headers based on the saved session.
-fn create_client() -> &'static reqwest::Client {
+pub(crate) fn create_client() -> &'static reqwest::Client {
 
     GLOBAL_CLIENT.get_or_init(|| {
         let mut headers = reqwest::header::HeaderMap::new();
@@ -59,6 +56,8 @@ fn create_client() -> &'static reqwest::Client {
 
         reqwest::Client::builder()
             .default_headers(headers)
+            .connect_timeout(Duration::from_secs(15))
+            .read_timeout(Duration::from_secs(30))
             .build()
             .expect("Failed to create HTTP client")
 
@@ -68,7 +67,7 @@ fn create_client() -> &'static reqwest::Client {
 
 }   
 
-async fn append_default_headers(req: RequestBuilder) -> RequestBuilder {
+pub(crate) async fn append_default_headers(req: RequestBuilder) -> RequestBuilder {
     let session = session::Session::load();
     let mut headers = reqwest::header::HeaderMap::new();
 
@@ -100,6 +99,136 @@ async fn append_default_headers(req: RequestBuilder) -> RequestBuilder {
     req.headers(headers)
 }
 
+/// Location: `$DATA_DIR/DesQTA/profiles/<id>/cache/`
+fn cache_dir() -> PathBuf {
+    let mut dir = session::profile_dir();
+    dir.push("cache");
+    dir
+}
+
+/// Path of the cache entry for `key`, or `None` if the cache directory cannot be created,
+/// in which case the response is simply not cached.
+fn cache_file(key: &str) -> Option<PathBuf> {
+    let dir = cache_dir();
+    if let Err(e) = fs::create_dir_all(&dir) {
+        eprintln!("[Desqta] Failed to create cache dir: {}", e);
+        return None;
+    }
+    Some(dir.join(format!("{}.json", key)))
+}
+
+/// A response body persisted to the on-disk cache.
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    url: String,
+    stored_at: u64,
+    data: String,
+}
+
+/// Response data along with where it came from.
+#[derive(Debug, Serialize)]
+pub struct ApiResponse {
+    pub data: String,
+    /// True if the data was served from the on-disk cache rather than the network.
+    pub from_cache: bool,
+    /// True if the cached data was past its TTL and only served because the request failed.
+    pub stale: bool,
+    /// Unix timestamp (seconds) of when the cached data was fetched.
+    pub cached_at: Option<u64>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// How long (in seconds) a response from `path` stays fresh, or `None` if it must never be cached.
+/// A TTL of 0 means the response is only ever served from cache when the network request fails.
+/// Only SEQTA's data endpoints are cached; state changes (saving messages, uploads,
+/// submissions), logging in or out and the heartbeat never are.
+fn cache_ttl(path: &str) -> Option<u64> {
+    match path {
+        p if p.starts_with("/seqta/student/load/timetable") => Some(6 * 60 * 60),
+        p if p.starts_with("/seqta/student/load/subjects") => Some(24 * 60 * 60),
+        p if p.starts_with("/seqta/student/load/courses") => Some(60 * 60),
+        p if p.starts_with("/seqta/student/load/reports") => Some(60 * 60),
+        p if p.starts_with("/seqta/student/load/prefs") => Some(60 * 60),
+        p if p.starts_with("/seqta/student/load/notices") => Some(10 * 60),
+        p if p.starts_with("/seqta/student/load/message") => Some(0),
+        p if p.starts_with("/seqta/student/load/") => Some(5 * 60),
+        p if p.starts_with("/seqta/student/assessment/list/") => Some(10 * 60),
+        p if p.starts_with("/seqta/student/assessment/get") => Some(10 * 60),
+        p if p.starts_with("/seqta/student/assessment/submissions/get") => Some(0),
+        p if p.starts_with("/seqta/student/dashlet/") => Some(10 * 60),
+        p if p.starts_with("/seqta/student/photo/") => Some(60),
+        _ => None,
+    }
+}
+
+/// Cache key: SHA-256 of the method, URL, sorted query parameters and request body.
+fn cache_key(
+    method: RequestMethod,
+    full_url: &str,
+    parameters: &Option<HashMap<String, String>>,
+    body: &Option<Value>,
+    is_image: bool,
+) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{:?}\n{}\n", method, full_url));
+
+    if let Some(params) = parameters {
+        let mut sorted: Vec<_> = params.iter().collect();
+        sorted.sort();
+        for (key, value) in sorted {
+            hasher.update(format!("{}={}&", key, value));
+        }
+    }
+    hasher.update("\n");
+
+    if method == RequestMethod::POST {
+        if let Some(body_data) = body {
+            hasher.update(body_data.to_string());
+        }
+    }
+    hasher.update(if is_image { "\nimage" } else { "\ntext" });
+
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn read_cache(key: &str) -> Option<CacheEntry> {
+    let contents = fs::read_to_string(cache_file(key)?).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+fn write_cache(key: &str, url: &str, data: &str) {
+    let entry = CacheEntry {
+        url: url.to_string(),
+        stored_at: now_secs(),
+        data: data.to_string(),
+    };
+    let Some(path) = cache_file(key) else { return };
+    if let Ok(json) = serde_json::to_string(&entry) {
+        if let Err(e) = fs::write(path, json) {
+            eprintln!("[Desqta] Failed to write response cache: {}", e);
+        }
+    }
+}
+
+/// Remove every cached response.
+pub fn clear_cache() -> std::io::Result<()> {
+    let dir = cache_dir();
+    if dir.exists() {
+        fs::remove_dir_all(dir)?;
+    }
+    Ok(())
+}
+
 #[tauri::command]
 pub async fn fetch_api_data(
     url: &str,
@@ -109,18 +238,145 @@ pub async fn fetch_api_data(
     parameters: Option<HashMap<String, String>>,
     is_image: bool,
     return_url: bool
-) -> Result<String, String> {
-    let client = create_client();
-    let session = session::Session::load();
+) -> Result<String, NetError> {
+    fetch_api_response(url, method, headers, body, parameters, is_image, return_url)
+        .await
+        .map(|response| response.data)
+}
+
+/// Same as `fetch_api_data`, but also reports whether the data came from the cache.
+/// Fresh cache entries are served without touching the network; stale entries are
+/// only served when the request itself fails (e.g. no network).
+#[tauri::command]
+pub async fn fetch_api_response(
+    url: &str,
+    method: RequestMethod,
+    headers: Option<HashMap<String, String>>,
+    body: Option<Value>,
+    parameters: Option<HashMap<String, String>>,
+    is_image: bool,
+    return_url: bool
+) -> Result<ApiResponse, NetError> {
+    fetch(url, method, headers, body, parameters, is_image, return_url, true).await
+}
+
+/// Fetch from the network even if a fresh cached copy exists, and fail instead of
+/// falling back to a stale one. The response still updates the cache and offline database.
+pub(crate) async fn refresh_api_data(
+    url: &str,
+    method: RequestMethod,
+    body: Option<Value>,
+    parameters: Option<HashMap<String, String>>,
+) -> Result<String, NetError> {
+    fetch(url, method, None, body, parameters, false, false, false)
+        .await
+        .map(|response| response.data)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn fetch(
+    url: &str,
+    method: RequestMethod,
+    headers: Option<HashMap<String, String>>,
+    body: Option<Value>,
+    parameters: Option<HashMap<String, String>>,
+    is_image: bool,
+    return_url: bool,
+    serve_cached: bool,
+) -> Result<ApiResponse, NetError> {
+    let (profile_id, session) = session::Session::load_active();
     let full_url = if url.starts_with("http") {
         url.to_string()
     } else {
         format!("{}{}", session.base_url.parse::<String>().unwrap(), url)
     };
 
+    // Redirect URLs for files are short-lived, so they are never cached
+    let ttl = if return_url {
+        None
+    } else {
+        Url::parse(&full_url)
+            .ok()
+            .and_then(|u| cache_ttl(u.path()))
+    };
+    let key = ttl.map(|_| cache_key(method, &full_url, &parameters, &body, is_image));
+
+    if let (Some(ttl), Some(key), true) = (ttl, &key, serve_cached) {
+        if let Some(entry) = read_cache(key) {
+            if now_secs().saturating_sub(entry.stored_at) < ttl {
+                return Ok(ApiResponse {
+                    data: entry.data,
+                    from_cache: true,
+                    stale: false,
+                    cached_at: Some(entry.stored_at),
+                });
+            }
+        }
+    }
+
+    // Kept for the offline database, which needs to know what was asked for
+    let request_body = body.clone();
+
+    match send_request(&full_url, method, headers, body, parameters, is_image, return_url, profile_id.as_deref()).await {
+        Ok(data) => {
+            if let Some(key) = &key {
+                write_cache(key, &full_url, &data);
+            }
+            if !is_image && !return_url && is_seqta_api(&full_url) {
+                if let Ok(parsed) = Url::parse(&full_url) {
+                    db::ingest(parsed.path(), request_body.as_ref(), &data);
+                    search::ingest(parsed.path(), request_body.as_ref(), &data);
+                }
+            }
+            if session.expired && is_seqta_api(&full_url) {
+                session::Session::mark_valid(profile_id.as_deref());
+            }
+            Ok(ApiResponse {
+                data,
+                from_cache: false,
+                stale: false,
+                cached_at: None,
+            })
+        }
+        // Only a read that never got an answer falls back to the cache
+        Err(e)
+            if serve_cached
+                && e.is_unreachable()
+                && Url::parse(&full_url).is_ok_and(|u| is_idempotent(method, u.path())) =>
+        {
+            if let Some(entry) = key.as_deref().and_then(read_cache) {
+                println!("[Desqta] Serving stale cache for {} ({})", full_url, e);
+                return Ok(ApiResponse {
+                    data: entry.data,
+                    from_cache: true,
+                    stale: true,
+                    cached_at: Some(entry.stored_at),
+                });
+            }
+            Err(e)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Perform the request against the network, returning the decoded body of a successful response.
+/// `profile_id` is the profile whose session the request was made with.
+#[allow(clippy::too_many_arguments)]
+async fn send_request(
+    full_url: &str,
+    method: RequestMethod,
+    headers: Option<HashMap<String, String>>,
+    body: Option<Value>,
+    parameters: Option<HashMap<String, String>>,
+    is_image: bool,
+    return_url: bool,
+    profile_id: Option<&str>,
+) -> Result<String, NetError> {
+    let client = create_client();
+
     let mut request = match method {
-        RequestMethod::GET => client.get(&full_url),
-        RequestMethod::POST => client.post(&full_url),
+        RequestMethod::GET => client.get(full_url),
+        RequestMethod::POST => client.post(full_url),
     };
 
     request = append_default_headers(request).await;
@@ -144,193 +400,270 @@ pub async fn fetch_api_data(
         }
     }
 
-    match request.send().await {
-    Ok(resp) => {
-        if is_image == true {
-            // Get the bytes (await and ? to bubble up errors)
-            let bytes = resp.bytes().await.map_err(|e| e.to_string())?;
-            // Encode to base64
-            let base64_str = general_purpose::STANDARD.encode(&bytes);
-            Ok(base64_str)
-        }
-        else if return_url == true {
-            Ok(String::from(resp.url().as_str()))
+    let retryable = Url::parse(full_url)
+        .map(|u| is_idempotent(method, u.path()))
+        .unwrap_or(false);
+    let policy = if retryable {
+        RetryPolicy::from_settings()
+    } else {
+        RetryPolicy::single_attempt()
+    };
+
+    let mut attempt = 0;
+    let resp = loop {
+        attempt += 1;
+        let attempt_request = request
+            .try_clone()
+            .ok_or_else(|| NetError::decode("request body cannot be cloned"))?;
+
+        match attempt_request.send().await {
+            Ok(resp) if is_retryable_status(resp.status()) => {
+                let status = resp.status();
+                let delay = retry_after(&resp).unwrap_or_else(|| policy.backoff(attempt));
+                if attempt >= policy.max_attempts || delay > policy.max_delay {
+                    let body = resp.text().await.unwrap_or_default();
+                    return Err(NetError::http(status.as_u16(), body).with_attempts(attempt));
+                }
+                println!(
+                    "[Desqta] {} returned {}, retrying in {:?} (attempt {}/{})",
+                    full_url, status, delay, attempt, policy.max_attempts
+                );
+                sleep(delay).await;
+            }
+            Ok(resp) => break resp,
+            Err(e) if (e.is_connect() || e.is_timeout()) && attempt < policy.max_attempts => {
+                let delay = policy.backoff(attempt);
+                println!(
+                    "[Desqta] {} failed ({}), retrying in {:?} (attempt {}/{})",
+                    full_url, e, delay, attempt, policy.max_attempts
+                );
+                sleep(delay).await;
+            }
+            Err(e) => return Err(NetError::from(e).with_attempts(attempt)),
         }
-        else {
-            let text = resp.text().await.map_err(|e| e.to_string())?;
-            Ok(text)
+    };
+
+    let is_seqta_api = is_seqta_api(full_url);
+    let status = resp.status();
+    if is_seqta_api && (status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN) {
+        return Err(session_expired(profile_id));
+    }
+    if !status.is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        return Err(NetError::http(status.as_u16(), body).with_attempts(attempt));
+    }
+    // Only a successful response is checked, so an HTML error page stays an HTTP error
+    if is_seqta_api && !is_image && !return_url && is_login_redirect(full_url, &resp) {
+        return Err(session_expired(profile_id));
+    }
+
+    if is_image {
+        // Get the bytes (await and ? to bubble up errors)
+        let bytes = resp.bytes().await?;
+        // Encode to base64
+        let base64_str = general_purpose::STANDARD.encode(&bytes);
+        Ok(base64_str)
+    }
+    else if return_url {
+        Ok(String::from(resp.url().as_str()))
+    }
+    else {
+        let text = resp.text().await?;
+        if is_seqta_api && is_unauthenticated_payload(&text) {
+            return Err(session_expired(profile_id));
         }
+        Ok(text)
     }
-    Err(e) => Err(format!("HTTP request failed: {e}")),
-}
 }
 
-#[tauri::command]
-pub async fn get_api_data(
-    url: &str,
-    parameters: HashMap<String, String>,
-) -> Result<String, String> {
-    fetch_api_data(url, RequestMethod::GET, None, None, Some(parameters), false, false).await
+fn is_seqta_api(url: &str) -> bool {
+    Url::parse(url)
+        .map(|u| u.path().starts_with("/seqta/student/"))
+        .unwrap_or(false)
 }
 
-#[tauri::command]
-pub async fn get_seqta_file(file_type: &str, uuid: &str) -> Result<String, String> {
-    let mut params = HashMap::new();
-    params.insert(String::from("type"), String::from(file_type));
-    params.insert(String::from("file"), String::from(uuid));
-    fetch_api_data("/seqta/student/load/file", RequestMethod::GET, None, None, Some(params), false, true).await
+/// Mark the session of `profile_id` as dead and, the first time it happens, tell the frontend.
+pub(crate) fn session_expired(profile_id: Option<&str>) -> NetError {
+    if session::Session::mark_expired(profile_id) {
+        crate::emit_session_expired();
+    }
+    NetError::SessionExpired
 }
 
-#[tauri::command]
-pub async fn upload_seqta_file(file_name: String, file_path: String) -> Result<String, String> {
-    
-    let client = create_client();
-    let session = session::Session::load();
-
-    // Read the file content
-    let file_content = fs::read(&file_path)
-        .map_err(|e| format!("Failed to read file: {}", e))?;
-    
-    let url = format!("{}/seqta/student/file/upload/xhr2", session.base_url.parse::<String>().unwrap());
-    let mut request = client.post(&url);
-    request = append_default_headers(request).await;
-
-    let url_filename: String = form_urlencoded::byte_serialize(&file_name.as_bytes()).collect();
-
-    // Set headers exactly like the web UI
-    request = request.header("X-File-Name", url_filename);
-    request = request.header("X-Accept-Mimes", "null");
-    request = request.header("X-Requested-With", "XMLHttpRequest");
-
-    match request.body(file_content).send().await {
-        Ok(resp) => {
-            let text = resp.text().await.map_err(|e| e.to_string())?;
-            Ok(text)
-        },
-        Err(e) => Err(format!("File upload failed: {e}")),
+/// Some SEQTA endpoints answer a dead session with `200 OK` and `{"status": "401"}`.
+fn is_unauthenticated_payload(text: &str) -> bool {
+    if !text.contains("401") {
+        return false;
     }
+    serde_json::from_str::<Value>(text)
+        .ok()
+        .and_then(|v| v.get("status").cloned())
+        .map(|status| status == json!("401") || status == json!(401))
+        .unwrap_or(false)
 }
 
-#[derive(Serialize)]
-pub struct FeedItem {
-    title: Option<String>,
-    link: Option<String>,
-    description: Option<String>,
-    pub_date: Option<String>,
-}
-#[derive(Serialize)]
-pub struct FeedResponse {
-    title: String,
-    items: Vec<FeedItem>,
+/// SEQTA answers API calls made with a dead JSESSIONID by redirecting to its HTML
+/// login page rather than returning an error status. Call this on successful responses
+/// only: an HTML body is a login page when JSON was asked for, not when the server failed.
+pub(crate) fn is_login_redirect(requested_url: &str, resp: &reqwest::Response) -> bool {
+    let content_type = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok());
+    is_login_page(requested_url, resp.url().path(), content_type)
 }
 
-#[tauri::command]
-pub async fn get_rss_feed(feed: &str) -> Result<Value, String> {
-    let client = Client::builder()
-        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/114.0.0.0 Safari/537.36")
-        .build()
-        .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
-
-    let res = client
-        .get(feed)
-        .send()
-        .await
-        .map_err(|e| format!("Request failed: {}", e))?;
-
-    let status = res.status();
-    let content = res
-        .text()
-        .await
-        .map_err(|e| format!("Failed to read response body: {}", e))?;
+/// `is_login_redirect` on the parts of the response it looks at.
+fn is_login_page(requested_url: &str, final_path: &str, content_type: Option<&str>) -> bool {
+    let requested_path = match Url::parse(requested_url) {
+        Ok(u) => u.path().to_string(),
+        Err(_) => return false,
+    };
 
-    if !status.is_success() {
-        return Err(format!(
-            "Request failed with status {}. Response body:\n{}",
-            status, content
-        ));
-    }
+    let final_path = final_path.to_lowercase();
+    let redirected_to_login = final_path != requested_path.to_lowercase()
+        && (final_path.contains("login") || final_path.contains("saml"));
 
-    let channel = Channel::read_from(content.as_bytes())
-        .map_err(|e| format!("Failed to parse RSS feed: {}", e))?;
+    let is_html = content_type.is_some_and(|v| v.starts_with("text/html"));
 
-    let json = channel_to_json(&channel)
-        .map_err(|e| format!("Failed to convert to JSON: {}", e))?;
+    redirected_to_login || is_html
+}
 
-    Ok(json)
+/// Retry behaviour for idempotent requests, configured through `Settings`.
+#[derive(Clone)]
+struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
 }
-pub fn channel_to_json(channel: &Channel) -> Result<Value> {
-    fn xml_to_json(elem: &Element) -> Value {
-        let text = elem.get_text();
-        let has_text = text.as_ref().map(|t| !t.trim().is_empty()).unwrap_or(false);
 
-        let has_attrs = !elem.attributes.is_empty();
-        let has_children = elem.children.iter().any(|c| matches!(c, XMLNode::Element(_)));
+impl RetryPolicy {
+    /// The policy from settings, reloaded at most every `RETRY_POLICY_TTL`.
+    fn from_settings() -> Self {
+        let mut cached = RETRY_POLICY.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((read_at, policy)) = cached.as_ref() {
+            if read_at.elapsed() < RETRY_POLICY_TTL {
+                return policy.clone();
+            }
+        }
+        let settings = Settings::load();
+        let policy = RetryPolicy {
+            max_attempts: settings.request_max_retries.saturating_add(1),
+            base_delay: Duration::from_millis(settings.request_retry_base_delay_ms),
+            max_delay: Duration::from_secs(30),
+        };
+        *cached = Some((Instant::now(), policy.clone()));
+        policy
+    }
 
-        if !has_attrs && !has_children && has_text {
-            return Value::String(text.unwrap().to_string());
+    fn single_attempt() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::from_secs(30),
         }
+    }
 
-        let mut map = serde_json::Map::new();
+    /// Exponential backoff with jitter: a random delay between half and all of
+    /// `base_delay * 2^(attempt - 1)`, capped at `max_delay`.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(1u32 << attempt.saturating_sub(1).min(16));
+        let millis = exponential.min(self.max_delay).as_millis() as u64;
+        Duration::from_millis(rand::thread_rng().gen_range(millis / 2..=millis))
+    }
+}
 
-        if has_attrs {
-            map.insert("@attributes".into(), json!(elem.attributes));
-        }
+/// SEQTA reads most data through POST. These paths only read, so repeating them is safe.
+/// The heartbeat is left out: a missed beat is simply sent again on the next tick.
+const READ_ONLY_POSTS: &[&str] = &[
+    "/seqta/student/load/",
+    "/seqta/student/assessment/list/",
+    "/seqta/student/assessment/get",
+    "/seqta/student/assessment/submissions/get",
+    "/seqta/student/dashlet/",
+];
+
+/// GETs and SEQTA's read-only POSTs can safely be repeated.
+fn is_idempotent(method: RequestMethod, path: &str) -> bool {
+    method == RequestMethod::GET || READ_ONLY_POSTS.iter().any(|p| path.starts_with(p))
+}
 
-        for child in &elem.children {
-            if let XMLNode::Element(child_elem) = child {
-                let child_json = xml_to_json(child_elem);
-                map.entry(child_elem.name.clone())
-                    .and_modify(|v| {
-                        if let Value::Array(arr) = v {
-                            arr.push(child_json.clone());
-                        } else {
-                            *v = Value::Array(vec![v.take(), child_json.clone()]);
-                        }
-                    })
-                    .or_insert(child_json);
-            }
-        }
+fn is_retryable_status(status: reqwest::StatusCode) -> bool {
+    matches!(status.as_u16(), 429 | 502 | 503 | 504)
+}
 
-        if has_text {
-            map.insert("text".into(), Value::String(text.unwrap().to_string()));
-        }
+/// Parse a `Retry-After` header given either as seconds or as an HTTP date.
+fn retry_after(resp: &reqwest::Response) -> Option<Duration> {
+    parse_retry_after(resp.headers().get(reqwest::header::RETRY_AFTER)?.to_str().ok()?)
+}
 
-        Value::Object(map)
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
     }
 
-    let xml_str = channel.to_string();
-    let root = Element::parse(Cursor::new(xml_str))
-        .map_err(|e| anyhow!("Failed to parse XML: {}", e))?;
+    httpdate::parse_http_date(value)
+        .ok()
+        .map(|date| date.duration_since(SystemTime::now()).unwrap_or(Duration::ZERO))
+}
 
-    let mut root_json = xml_to_json(&root);
+/// Result of actively checking the saved session against SEQTA.
+#[derive(Debug, Serialize)]
+pub struct SessionStatus {
+    pub valid: bool,
+    pub base_url: String,
+    /// Unix timestamp (seconds) at which the JSESSIONID cookie expires, as captured at login.
+    pub expires_at: Option<i64>,
+}
 
-    // Parse item elements into feeds array using flexible xml_to_json
-    let feeds: Vec<Value> = root
-        .get_child("channel")
-        .map(|channel_elem| {
-            channel_elem
-                .children
-                .iter()
-                .filter_map(|node| {
-                    if let XMLNode::Element(child) = node {
-                        if child.name == "item" {
-                            Some(xml_to_json(child))
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    }
-                })
-                .collect()
-        })
-        .unwrap_or_default();
+/// Ping `/seqta/student/heartbeat` to find out whether SEQTA still accepts the saved session.
+/// Bypasses the response cache; network failures are returned as errors because they say
+/// nothing about the session itself.
+#[tauri::command]
+pub async fn validate_session() -> Result<SessionStatus, NetError> {
+    let (profile_id, session) = session::Session::load_active();
+    let mut status = SessionStatus {
+        valid: false,
+        base_url: session.base_url.clone(),
+        expires_at: session.expires_at,
+    };
+    if session.base_url.is_empty() || session.jsessionid.is_empty() {
+        return Ok(status);
+    }
 
-    if let Value::Object(ref mut map) = root_json {
-        map.insert("feeds".to_string(), Value::Array(feeds));
+    let full_url = format!("{}/seqta/student/heartbeat", session.base_url);
+    let body = json!({
+        "timestamp": "1970-01-01 00:00:00.0",
+        "hash": "#?page=/home",
+    });
+    match send_request(&full_url, RequestMethod::POST, None, Some(body), None, false, false, profile_id.as_deref()).await {
+        Ok(_) => {
+            session::Session::mark_valid(profile_id.as_deref());
+            status.valid = true;
+            Ok(status)
+        }
+        Err(NetError::SessionExpired) => Ok(status),
+        Err(e) => Err(e),
     }
+}
 
-    Ok(root_json)
+#[tauri::command]
+pub async fn get_api_data(
+    url: &str,
+    parameters: HashMap<String, String>,
+) -> Result<String, NetError> {
+    fetch_api_data(url, RequestMethod::GET, None, None, Some(parameters), false, false).await
+}
+
+#[tauri::command]
+pub async fn get_seqta_file(file_type: &str, uuid: &str) -> Result<String, NetError> {
+    let mut params = HashMap::new();
+    params.insert(String::from("type"), String::from(file_type));
+    params.insert(String::from("file"), String::from(uuid));
+    fetch_api_data("/seqta/student/load/file", RequestMethod::GET, None, None, Some(params), false, true).await
 }
 
 /// Open a login window and harvest the cookie once the user signs in.
@@ -338,14 +671,10 @@ pub fn channel_to_json(channel: &Channel) -> Result<Value> {
 pub async fn open_url(app: tauri::AppHandle, url: String) -> Result<(), String>{
     use tauri::{WebviewUrl, WebviewWindowBuilder};
 
-    let http_url;
-
-    match url.starts_with("https://") {
-        true => http_url = url.clone(),
-        false => {
-            http_url = format!("https://{}", url.clone());
-        }
-    }
+    let http_url = match url.starts_with("https://") {
+        true => url.clone(),
+        false => format!("https://{}", url.clone()),
+    };
 
     let parsed_url = match Url::parse(&http_url) {
         Ok(u) => u,
@@ -373,7 +702,7 @@ pub async fn post_api_data(
     url: &str,
     data: Value,
     parameters: HashMap<String, String>,
-) -> Result<String, String> {
+) -> Result<String, NetError> {
     fetch_api_data(url, RequestMethod::POST, None, Some(data), Some(parameters), false, false).await
 }
 
@@ -383,6 +712,80 @@ pub async fn clear_session() -> Result<(), String> {
     // Send logout request first
     let _ = get_api_data("/saml2?logout", HashMap::new()).await;
     
-    // Then clear the session file
-    session::Session::clear_file().map_err(|e| e.to_string())
-}
\ No newline at end of file
+    // Cached responses belong to the old session
+    if let Err(e) = clear_cache() {
+        eprintln!("[Desqta] Failed to clear response cache: {}", e);
+    }
+
+    // Then log the active profile out
+    session::Session::clear().map_err(|e| e.to_string())
+}
+
+/// Remove every cached SEQTA response from disk.
+#[tauri::command]
+pub fn clear_api_cache() -> Result<(), String> {
+    clear_cache().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caches_only_seqta_data_endpoints() {
+        assert_eq!(cache_ttl("/seqta/student/load/timetable"), Some(6 * 60 * 60));
+        assert_eq!(cache_ttl("/seqta/student/load/notices"), Some(10 * 60));
+        assert_eq!(cache_ttl("/seqta/student/load/message/people"), Some(0));
+        assert_eq!(cache_ttl("/seqta/student/load/portals"), Some(5 * 60));
+        assert_eq!(cache_ttl("/seqta/student/assessment/submissions/get"), Some(0));
+    }
+
+    #[test]
+    fn never_caches_auth_state_changes_or_other_paths() {
+        for path in [
+            "/seqta/student/login",
+            "/seqta/student/heartbeat",
+            "/saml2",
+            "/seqta/student/save/message",
+            "/seqta/student/file/upload/xhr",
+            "/seqta/student/assessment/submissions/save",
+            "/",
+        ] {
+            assert_eq!(cache_ttl(path), None, "{}", path);
+        }
+    }
+
+    #[test]
+    fn only_reads_are_idempotent() {
+        assert!(is_idempotent(RequestMethod::GET, "/seqta/student/anything"));
+        assert!(is_idempotent(RequestMethod::POST, "/seqta/student/load/timetable"));
+        assert!(is_idempotent(RequestMethod::POST, "/seqta/student/assessment/submissions/get"));
+        assert!(!is_idempotent(RequestMethod::POST, "/seqta/student/save/message"));
+        assert!(!is_idempotent(RequestMethod::POST, "/seqta/student/assessment/submissions/save"));
+        assert!(!is_idempotent(RequestMethod::POST, "/seqta/student/login"));
+        assert!(!is_idempotent(RequestMethod::POST, "/seqta/student/heartbeat"));
+    }
+
+    #[test]
+    fn retry_after_takes_seconds_or_a_date() {
+        assert_eq!(parse_retry_after(" 120 "), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+        let soon = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(90));
+        let delay = parse_retry_after(&soon).unwrap();
+        assert!(delay > Duration::from_secs(80) && delay <= Duration::from_secs(90));
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn login_page_is_a_redirect_or_html() {
+        let url = "https://school.example/seqta/student/load/notices";
+        let json = Some("application/json; charset=utf-8");
+        assert!(!is_login_page(url, "/seqta/student/load/notices", json));
+        assert!(is_login_page(url, "/seqta/student/login", json));
+        assert!(is_login_page(url, "/saml2/sso", None));
+        assert!(is_login_page(url, "/seqta/student/load/notices", Some("text/html;charset=UTF-8")));
+        // Asking for the login endpoint itself is not a redirect to it
+        assert!(!is_login_page("https://school.example/seqta/student/login", "/seqta/student/login", json));
+        assert!(!is_login_page("not a url", "/login", Some("text/html")));
+    }
+}
@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::{fs, path::PathBuf};
+use tauri::AppHandle;
+use tauri_plugin_notification::NotificationExt;
+
+use crate::seqta::{Assessment, MessageSummary, Notice, Report};
+use crate::session;
+use crate::settings::Settings;
+
+/// Notices and messages remembered per category; older entries are forgotten first.
+const MAX_REMEMBERED: usize = 1000;
+/// Above this many new items in one sync, a single summary notification is shown instead.
+const MAX_INDIVIDUAL: usize = 3;
+
+const MARKS_RELEASED: &str = "MARKS_RELEASED";
+
+/// What has already been notified about, so restarts do not repeat notifications.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SeenItems {
+    /// Assessment id -> last seen status.
+    #[serde(default)]
+    assessments: HashMap<String, Option<String>>,
+    #[serde(default)]
+    notices: Vec<String>,
+    #[serde(default)]
+    messages: Vec<i64>,
+    #[serde(default)]
+    reports: Vec<String>,
+    /// Categories that have been recorded at least once. The first sync of a category
+    /// only records what exists, so a fresh install does not raise a notification per item.
+    #[serde(default)]
+    initialised: Vec<String>,
+}
+
+/// Location: `$DATA_DIR/DesQTA/profiles/<id>/notified.json`
+fn seen_file() -> PathBuf {
+    let mut dir = session::profile_dir();
+    dir.push("notified.json");
+    dir
+}
+
+impl SeenItems {
+    fn load() -> Self {
+        fs::read_to_string(seen_file())
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        if let Err(e) = fs::write(seen_file(), serde_json::to_string(self).unwrap()) {
+            eprintln!("[Desqta] Failed to save notification state: {}", e);
+        }
+    }
+
+    /// Mark `category` as recorded, returning whether it already was.
+    fn initialise(&mut self, category: &str) -> bool {
+        if self.initialised.iter().any(|c| c == category) {
+            return true;
+        }
+        self.initialised.push(category.to_string());
+        false
+    }
+}
+
+fn remember<T>(list: &mut Vec<T>, item: T) {
+    list.push(item);
+    if list.len() > MAX_REMEMBERED {
+        list.drain(..list.len() - MAX_REMEMBERED);
+    }
+}
+
+fn show(app: &AppHandle, title: &str, body: &str) {
+    if let Err(e) = app.notification().builder().title(title).body(body).show() {
+        eprintln!("[Desqta] Failed to show notification: {}", e);
+    }
+}
+
+/// Show one notification per item, or a single summary if there are too many.
+fn show_all(app: &AppHandle, summary: &str, notifications: Vec<(String, String)>) {
+    if notifications.len() > MAX_INDIVIDUAL {
+        show(app, summary, &format!("{} new items in DesQTA", notifications.len()));
+    } else {
+        for (title, body) in notifications {
+            show(app, &title, &body);
+        }
+    }
+}
+
+/// Notify about assessments that are new or have had marks released since last seen.
+pub(crate) fn assessments_fetched(app: &AppHandle, assessments: &[Assessment]) {
+    let settings = Settings::load();
+    let mut seen = SeenItems::load();
+    let notify = seen.initialise("assessments") && settings.reminders_enabled;
+
+    let mut new_assessments = Vec::new();
+    let mut released = Vec::new();
+    for a in assessments {
+        let id = a.id.to_string();
+        let is_released = a.status.as_deref() == Some(MARKS_RELEASED);
+        match seen.assessments.get(&id) {
+            None if is_released => released.push(a),
+            None => new_assessments.push(a),
+            Some(status) if is_released && status.as_deref() != Some(MARKS_RELEASED) => released.push(a),
+            Some(_) => {}
+        }
+        seen.assessments.insert(id, a.status.clone());
+    }
+    seen.save();
+
+    if !notify {
+        return;
+    }
+    if settings.notify_new_assessments {
+        let items = new_assessments
+            .iter()
+            .map(|a| (format!("New assessment: {}", a.subject), format!("{} (due {})", a.title, a.due.get(..10).unwrap_or(&a.due))))
+            .collect();
+        show_all(app, "New assessments", items);
+    }
+    if settings.notify_marks_released {
+        let items = released
+            .iter()
+            .map(|a| (format!("Marks released: {}", a.subject), a.title.clone()))
+            .collect();
+        show_all(app, "Marks released", items);
+    }
+}
+
+/// Notify about notices that have not been seen before.
+pub(crate) fn notices_fetched(app: &AppHandle, notices: &[Notice]) {
+    let settings = Settings::load();
+    let mut seen = SeenItems::load();
+    let notify = seen.initialise("notices") && settings.reminders_enabled && settings.notify_notices;
+
+    let mut items = Vec::new();
+    for n in notices {
+        let key = format!("{}|{}|{}", n.title, n.staff, n.label_title);
+        if !seen.notices.contains(&key) {
+            items.push((format!("Notice: {}", n.title), n.staff.clone()));
+            remember(&mut seen.notices, key);
+        }
+    }
+    seen.save();
+
+    if notify {
+        show_all(app, "New notices", items);
+    }
+}
+
+/// Notify about unread inbox messages that have not been seen before.
+pub(crate) fn messages_fetched(app: &AppHandle, messages: &[MessageSummary]) {
+    let settings = Settings::load();
+    let mut seen = SeenItems::load();
+    let notify = seen.initialise("messages") && settings.reminders_enabled && settings.notify_messages;
+
+    let mut items = Vec::new();
+    for m in messages {
+        if !seen.messages.contains(&m.id) {
+            if !m.read {
+                items.push((format!("Message from {}", m.sender), m.subject.clone()));
+            }
+            remember(&mut seen.messages, m.id);
+        }
+    }
+    seen.save();
+
+    if notify {
+        show_all(app, "New messages", items);
+    }
+}
+
+/// Notify about reports that have been published since last seen.
+pub(crate) fn reports_fetched(app: &AppHandle, reports: &[Report]) {
+    let settings = Settings::load();
+    let mut seen = SeenItems::load();
+    let notify = seen.initialise("reports") && settings.reminders_enabled && settings.notify_reports;
+
+    let mut items = Vec::new();
+    for r in reports {
+        if !seen.reports.contains(&r.uuid) {
+            items.push(("New report available".to_string(), format!("{} {} {}", r.year, r.terms, r.types)));
+            remember(&mut seen.reports, r.uuid.clone());
+        }
+    }
+    seen.save();
+
+    if notify {
+        show_all(app, "New reports", items);
+    }
+}
@@ -0,0 +1,197 @@
+use chrono::Local;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Cursor;
+use tauri::AppHandle;
+use tauri_plugin_dialog::DialogExt;
+use url::Url;
+use xmltree::{Element, EmitterConfig, XMLNode};
+
+use crate::neterror::NetError;
+use crate::settings::{Feed, Settings};
+
+#[derive(Debug, Serialize)]
+pub struct OpmlImport {
+    /// Feeds that were not subscribed before.
+    pub added: usize,
+    /// Feeds already subscribed, or listed more than once in the file.
+    pub merged: usize,
+    /// Every subscribed feed after the import.
+    pub feeds: Vec<Feed>,
+}
+
+/// Comparison key, so the same feed written with a different host case or a trailing
+/// slash is recognised as a duplicate.
+pub(crate) fn feed_key(url: &str) -> String {
+    let url = url.trim();
+    Url::parse(url)
+        .map(|u| u.to_string())
+        .unwrap_or_else(|_| url.to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+fn attr<'a>(elem: &'a Element, name: &str) -> Option<&'a str> {
+    elem.attributes.get(name).map(|v| v.trim()).filter(|v| !v.is_empty())
+}
+
+/// Feeds under `parent`. Outlines without an `xmlUrl` are folders, and the path of folders
+/// above a feed becomes its category.
+fn collect_outlines(parent: &Element, folders: &mut Vec<String>, out: &mut Vec<Feed>) {
+    for child in &parent.children {
+        let XMLNode::Element(outline) = child else { continue };
+        if outline.name != "outline" {
+            continue;
+        }
+        let name = attr(outline, "title").or_else(|| attr(outline, "text"));
+        match attr(outline, "xmlUrl") {
+            Some(url) => out.push(Feed {
+                url: url.to_string(),
+                title: name.map(|n| n.to_string()),
+                category: if folders.is_empty() {
+                    // Flat exports put the folder in a comma-separated `category` attribute instead
+                    attr(outline, "category")
+                        .and_then(|c| c.split(',').next())
+                        .map(|c| c.trim().trim_matches('/').to_string())
+                        .filter(|c| !c.is_empty())
+                } else {
+                    Some(folders.join("/"))
+                },
+            }),
+            None => {
+                folders.push(name.unwrap_or("Untitled").replace('/', "-"));
+                collect_outlines(outline, folders, out);
+                folders.pop();
+            }
+        }
+    }
+}
+
+pub(crate) fn parse_opml(content: &str) -> Result<Vec<Feed>, NetError> {
+    let root = Element::parse(Cursor::new(content.trim_start_matches('\u{feff}')))
+        .map_err(|e| NetError::decode(format!("Failed to parse OPML: {}", e)))?;
+    if root.name != "opml" {
+        return Err(NetError::decode(format!("Expected an OPML file, found <{}>", root.name)));
+    }
+    let body = root
+        .get_child("body")
+        .ok_or_else(|| NetError::decode("OPML file has no <body>"))?;
+    let mut feeds = Vec::new();
+    collect_outlines(body, &mut Vec::new(), &mut feeds);
+    Ok(feeds)
+}
+
+/// Add `imported` to `existing`, merging duplicate URLs. A title or category the user
+/// already set is kept; the import only fills in missing ones.
+fn merge_feeds(existing: &mut Vec<Feed>, imported: Vec<Feed>) -> (usize, usize) {
+    let mut index: HashMap<String, usize> =
+        existing.iter().enumerate().map(|(i, feed)| (feed_key(&feed.url), i)).collect();
+    let (mut added, mut merged) = (0, 0);
+    for feed in imported {
+        let key = feed_key(&feed.url);
+        match index.get(&key) {
+            Some(&i) => {
+                let current = &mut existing[i];
+                if current.title.as_deref().is_none_or(str::is_empty) {
+                    current.title = feed.title;
+                }
+                if current.category.as_deref().is_none_or(str::is_empty) {
+                    current.category = feed.category;
+                }
+                merged += 1;
+            }
+            None => {
+                index.insert(key, existing.len());
+                existing.push(feed);
+                added += 1;
+            }
+        }
+    }
+    (added, merged)
+}
+
+fn outline(text: &str) -> Element {
+    let mut elem = Element::new("outline");
+    elem.attributes.insert("text".to_string(), text.to_string());
+    elem.attributes.insert("title".to_string(), text.to_string());
+    elem
+}
+
+fn text_element(name: &str, text: String) -> XMLNode {
+    let mut elem = Element::new(name);
+    elem.children.push(XMLNode::Text(text));
+    XMLNode::Element(elem)
+}
+
+/// OPML 2.0 for `feeds`, with categories written as nested folder outlines.
+pub(crate) fn build_opml(feeds: &[Feed]) -> String {
+    let mut body = Element::new("body");
+    for feed in feeds {
+        let mut parent = &mut body;
+        for folder in feed.category.iter().flat_map(|c| c.split('/')).map(str::trim).filter(|f| !f.is_empty()) {
+            let existing = parent.children.iter().position(|c| {
+                matches!(c, XMLNode::Element(e) if !e.attributes.contains_key("xmlUrl")
+                    && e.attributes.get("text").map(String::as_str) == Some(folder))
+            });
+            let position = existing.unwrap_or_else(|| {
+                parent.children.push(XMLNode::Element(outline(folder)));
+                parent.children.len() - 1
+            });
+            parent = match &mut parent.children[position] {
+                XMLNode::Element(e) => e,
+                _ => unreachable!(),
+            };
+        }
+        let mut elem = outline(feed.title.as_deref().filter(|t| !t.is_empty()).unwrap_or(&feed.url));
+        elem.attributes.insert("type".to_string(), "rss".to_string());
+        elem.attributes.insert("xmlUrl".to_string(), feed.url.clone());
+        parent.children.push(XMLNode::Element(elem));
+    }
+
+    let mut head = Element::new("head");
+    head.children.push(text_element("title", "DesQTA feeds".to_string()));
+    head.children.push(text_element("dateCreated", Local::now().to_rfc2822()));
+
+    let mut root = Element::new("opml");
+    root.attributes.insert("version".to_string(), "2.0".to_string());
+    root.children.push(XMLNode::Element(head));
+    root.children.push(XMLNode::Element(body));
+
+    let mut out = Vec::new();
+    if let Err(e) = root.write_with_config(&mut out, EmitterConfig::new().perform_indent(true)) {
+        eprintln!("[Desqta] Failed to write OPML: {}", e);
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// Subscribe to every feed in an OPML file. Nested folders become categories and feeds
+/// that are already subscribed are merged rather than added twice.
+#[tauri::command]
+pub fn import_opml(path: String) -> Result<OpmlImport, NetError> {
+    let imported = parse_opml(&std::fs::read_to_string(&path)?)?;
+    let mut settings = Settings::load();
+    let (added, merged) = merge_feeds(&mut settings.feeds, imported);
+    settings.save()?;
+    println!("[Desqta] Imported OPML {}: {} feeds added, {} merged", path, added, merged);
+    Ok(OpmlImport { added, merged, feeds: settings.feeds })
+}
+
+/// Save the subscribed feeds to an OPML file the user picks. Returns the path, or `None`
+/// if the dialog was cancelled.
+#[tauri::command]
+pub async fn export_opml(app: AppHandle) -> Result<Option<String>, NetError> {
+    let settings = Settings::load();
+    let Some(path) = app
+        .dialog()
+        .file()
+        .add_filter("OPML", &["opml", "xml"])
+        .set_file_name("desqta-feeds.opml")
+        .blocking_save_file()
+    else {
+        return Ok(None);
+    };
+    let path = path.into_path().map_err(|e| NetError::Io { message: e.to_string() })?;
+    std::fs::write(&path, build_opml(&settings.feeds))?;
+    println!("[Desqta] Exported {} feeds to {}", settings.feeds.len(), path.display());
+    Ok(Some(path.to_string_lossy().to_string()))
+}
@@ -0,0 +1,363 @@
+use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::{fs, path::PathBuf};
+use tauri::{AppHandle, Emitter};
+use tauri_plugin_notification::NotificationExt;
+use tokio::time::{sleep, Duration};
+
+use crate::db;
+use crate::seqta::{self, Assessment};
+use crate::session;
+use crate::settings::Settings;
+
+/// How often due reminders are checked for.
+const TICK: Duration = Duration::from_secs(60);
+/// Refetch upcoming assessments if the offline copy is older than this (seconds).
+const MAX_ASSESSMENT_AGE: i64 = 60 * 60;
+/// SEQTA due dates usually have no time; assume the start of the school day.
+const DEFAULT_DUE_TIME: (u32, u32) = (8, 30);
+
+/// Serialises read-modify-write of `reminders.json` between the scheduler and commands.
+static LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReminderStatus {
+    Pending,
+    Fired,
+    Dismissed,
+}
+
+/// One notification scheduled ahead of an assessment's due date.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reminder {
+    /// `<assessment id>:<lead minutes>`
+    pub id: String,
+    pub assessment_id: i64,
+    pub title: String,
+    pub subject: String,
+    /// Unix timestamps (seconds).
+    pub due_at: i64,
+    pub fire_at: i64,
+    pub lead_mins: u64,
+    pub status: ReminderStatus,
+    #[serde(default)]
+    pub snoozed_until: Option<i64>,
+}
+
+impl Reminder {
+    fn next_fire(&self) -> i64 {
+        self.snoozed_until.unwrap_or(self.fire_at)
+    }
+}
+
+/// Location: `$DATA_DIR/DesQTA/profiles/<id>/reminders.json`
+fn reminders_file() -> PathBuf {
+    let mut dir = session::profile_dir();
+    dir.push("reminders.json");
+    dir
+}
+
+fn load() -> Vec<Reminder> {
+    fs::read_to_string(reminders_file())
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save(reminders: &[Reminder]) -> Result<(), String> {
+    fs::write(reminders_file(), serde_json::to_string(reminders).unwrap()).map_err(|e| e.to_string())
+}
+
+fn now_secs() -> i64 {
+    Local::now().timestamp()
+}
+
+/// Parse SEQTA's due date, which is either a plain date or a local date and time.
+fn due_timestamp(due: &str) -> Option<i64> {
+    let naive = NaiveDateTime::parse_from_str(due, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(due, "%Y-%m-%d %H:%M:%S"))
+        .ok()
+        .or_else(|| {
+            let date = NaiveDate::parse_from_str(due.get(..10)?, "%Y-%m-%d").ok()?;
+            let (h, m) = DEFAULT_DUE_TIME;
+            Some(date.and_time(NaiveTime::from_hms_opt(h, m, 0)?))
+        })?;
+    Local.from_local_datetime(&naive).earliest().map(|dt| dt.timestamp())
+}
+
+/// Bring the schedule in line with the current upcoming assessments and lead times.
+fn reschedule(reminders: &mut Vec<Reminder>, assessments: &[Assessment], lead_times: &[u64], now: i64) {
+    // Assessments that are no longer upcoming need no more reminders, unless one was
+    // snoozed on purpose
+    reminders.retain(|r| {
+        r.status != ReminderStatus::Pending
+            || r.snoozed_until.is_some()
+            || assessments.iter().any(|a| a.id == r.assessment_id)
+    });
+
+    for a in assessments {
+        let Some(due_at) = due_timestamp(&a.due) else { continue };
+        for &lead_mins in lead_times {
+            let id = format!("{}:{}", a.id, lead_mins);
+            let fire_at = due_at - (lead_mins as i64) * 60;
+            match reminders.iter_mut().find(|r| r.id == id) {
+                // The due date moved; schedule this lead time again
+                Some(r) if r.due_at != due_at => {
+                    r.due_at = due_at;
+                    r.fire_at = fire_at;
+                    r.snoozed_until = None;
+                    if r.status != ReminderStatus::Dismissed {
+                        r.status = ReminderStatus::Pending;
+                    }
+                }
+                Some(r) => {
+                    r.title = a.title.clone();
+                    r.subject = a.subject.clone();
+                }
+                // Lead times that had already passed when the assessment was set are skipped
+                None if fire_at > now => reminders.push(Reminder {
+                    id,
+                    assessment_id: a.id,
+                    title: a.title.clone(),
+                    subject: a.subject.clone(),
+                    due_at,
+                    fire_at,
+                    lead_mins,
+                    status: ReminderStatus::Pending,
+                    snoozed_until: None,
+                }),
+                None => {}
+            }
+        }
+    }
+
+    // Reminders for lead times that were removed from settings are dropped, and
+    // finished ones are forgotten a week after the assessment was due
+    reminders.retain(|r| match r.status {
+        ReminderStatus::Pending => r.snoozed_until.is_some() || lead_times.contains(&r.lead_mins),
+        _ => r.due_at > now - 7 * 24 * 60 * 60,
+    });
+}
+
+/// Reminders that should fire now. If several for one assessment were missed while
+/// the app was closed, only the latest one is returned and the rest are marked fired.
+/// Reminders missed until after the due date are dropped, but a snoozed one still fires.
+fn take_due(reminders: &mut [Reminder], now: i64) -> Vec<Reminder> {
+    let mut due: Vec<usize> = reminders
+        .iter()
+        .enumerate()
+        .filter(|(_, r)| r.status == ReminderStatus::Pending && r.next_fire() <= now)
+        .map(|(i, _)| i)
+        .collect();
+    due.sort_by_key(|&i| std::cmp::Reverse(reminders[i].next_fire()));
+
+    let mut fired: Vec<Reminder> = Vec::new();
+    for i in due {
+        let r = &mut reminders[i];
+        let live = r.snoozed_until.is_some() || r.due_at > now;
+        r.status = ReminderStatus::Fired;
+        r.snoozed_until = None;
+        if live && !fired.iter().any(|f| f.assessment_id == r.assessment_id) {
+            fired.push(r.clone());
+        }
+    }
+    fired
+}
+
+/// Fire reminder `id` again at `until`, whether or not it has fired already.
+fn snooze(reminders: &mut [Reminder], id: &str, until: i64) -> Result<(), String> {
+    let reminder = reminders
+        .iter_mut()
+        .find(|r| r.id == id)
+        .ok_or_else(|| format!("No reminder with id {}", id))?;
+    reminder.status = ReminderStatus::Pending;
+    reminder.snoozed_until = Some(until);
+    Ok(())
+}
+
+/// Dismiss reminder `id`, and with `all` every other reminder for the same assessment.
+fn dismiss(reminders: &mut [Reminder], id: &str, all: bool) -> Result<(), String> {
+    let assessment_id = reminders
+        .iter()
+        .find(|r| r.id == id)
+        .map(|r| r.assessment_id)
+        .ok_or_else(|| format!("No reminder with id {}", id))?;
+    for r in reminders.iter_mut() {
+        if r.id == id || (all && r.assessment_id == assessment_id) {
+            r.status = ReminderStatus::Dismissed;
+            r.snoozed_until = None;
+        }
+    }
+    Ok(())
+}
+
+fn describe_lead(mins: i64) -> String {
+    match mins {
+        m if m >= 24 * 60 => {
+            let days = (m + 12 * 60) / (24 * 60);
+            format!("in {} day{}", days, if days == 1 { "" } else { "s" })
+        }
+        m if m >= 60 => {
+            let hours = (m + 30) / 60;
+            format!("in {} hour{}", hours, if hours == 1 { "" } else { "s" })
+        }
+        m if m > 0 => format!("in {} minutes", m),
+        _ => "now".to_string(),
+    }
+}
+
+async fn upcoming_assessments() -> Vec<Assessment> {
+    let fresh = db::get_last_synced("assessments".to_string())
+        .ok()
+        .flatten()
+        .is_some_and(|synced| now_secs() - synced < MAX_ASSESSMENT_AGE);
+    if !fresh && session::Session::exists() {
+        // Stored in the offline database as a side effect
+        if let Err(e) = seqta::upcoming_assessments(false).await {
+            eprintln!("[Desqta] Failed to refresh assessments for reminders: {}", e);
+        }
+    }
+    db::get_offline_assessments(true).unwrap_or_default()
+}
+
+/// Spawn the scheduler that fires assessment reminders, including while the window is
+/// hidden to the tray or the app was started minimised at login.
+pub fn start(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let settings = Settings::load();
+            let assessments = upcoming_assessments().await;
+
+            let fired = {
+                let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+                let now = now_secs();
+                let mut reminders = load();
+                reschedule(&mut reminders, &assessments, &settings.reminder_lead_times_mins, now);
+                let fired = take_due(&mut reminders, now);
+                if let Err(e) = save(&reminders) {
+                    eprintln!("[Desqta] Failed to save reminders: {}", e);
+                }
+                fired
+            };
+
+            if settings.reminders_enabled {
+                for r in fired {
+                    let body = format!("{} is due {}", r.title, describe_lead((r.due_at - now_secs()) / 60));
+                    if let Err(e) = app.notification().builder().title(&r.subject).body(&body).show() {
+                        eprintln!("[Desqta] Failed to show reminder: {}", e);
+                    }
+                    let _ = app.emit("reminder-fired", &r);
+                }
+            }
+
+            sleep(TICK).await;
+        }
+    });
+}
+
+/// Reminders that have not fired yet, soonest first.
+#[tauri::command]
+pub fn list_reminders() -> Vec<Reminder> {
+    let mut reminders: Vec<Reminder> = load()
+        .into_iter()
+        .filter(|r| r.status == ReminderStatus::Pending)
+        .collect();
+    reminders.sort_by_key(|r| r.next_fire());
+    reminders
+}
+
+/// Fire a reminder again after `minutes`, whether or not it has fired already.
+#[tauri::command]
+pub fn snooze_reminder(id: String, minutes: u64) -> Result<(), String> {
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut reminders = load();
+    snooze(&mut reminders, &id, now_secs() + (minutes as i64) * 60)?;
+    save(&reminders)
+}
+
+/// Stop a reminder from firing. With `all`, every remaining reminder for the same
+/// assessment is dismissed too.
+#[tauri::command]
+pub fn dismiss_reminder(id: String, all: Option<bool>) -> Result<(), String> {
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut reminders = load();
+    dismiss(&mut reminders, &id, all.unwrap_or(false))?;
+    save(&reminders)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: i64 = 60 * 60;
+
+    fn assessment(id: i64, due: &str) -> Assessment {
+        Assessment {
+            id,
+            title: "Essay".to_string(),
+            subject: "English".to_string(),
+            code: "ENG".to_string(),
+            due: due.to_string(),
+            status: None,
+            metaclass_id: 1,
+            programme_id: 1,
+            colour: None,
+        }
+    }
+
+    fn find<'a>(reminders: &'a [Reminder], id: &str) -> &'a Reminder {
+        reminders.iter().find(|r| r.id == id).unwrap()
+    }
+
+    #[test]
+    fn moved_due_date_reschedules_reminders() {
+        let due = due_timestamp("2030-05-10").unwrap();
+        let mut reminders = Vec::new();
+        reschedule(&mut reminders, &[assessment(1, "2030-05-10")], &[60], due - 2 * HOUR);
+        let fired = take_due(&mut reminders, due - HOUR);
+        assert_eq!(fired.len(), 1);
+        assert_eq!(find(&reminders, "1:60").status, ReminderStatus::Fired);
+
+        reschedule(&mut reminders, &[assessment(1, "2030-05-12")], &[60], due);
+        let moved = find(&reminders, "1:60");
+        assert_eq!(moved.status, ReminderStatus::Pending);
+        assert_eq!(moved.due_at, due_timestamp("2030-05-12").unwrap());
+        assert_eq!(moved.fire_at, moved.due_at - HOUR);
+    }
+
+    #[test]
+    fn dismissed_reminders_do_not_fire() {
+        let due = due_timestamp("2030-05-10").unwrap();
+        let mut reminders = Vec::new();
+        let assessments = [assessment(1, "2030-05-10"), assessment(2, "2030-05-10")];
+        reschedule(&mut reminders, &assessments, &[60, 120], due - 3 * HOUR);
+
+        dismiss(&mut reminders, "1:60", true).unwrap();
+        dismiss(&mut reminders, "2:60", false).unwrap();
+        assert_eq!(find(&reminders, "1:120").status, ReminderStatus::Dismissed);
+        assert_eq!(find(&reminders, "2:120").status, ReminderStatus::Pending);
+        assert!(dismiss(&mut reminders, "3:60", false).is_err());
+
+        let fired = take_due(&mut reminders, due - HOUR);
+        assert_eq!(fired.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), ["2:120"]);
+    }
+
+    #[test]
+    fn snoozed_reminder_fires_even_after_the_due_date() {
+        let due = due_timestamp("2030-05-10").unwrap();
+        let mut reminders = Vec::new();
+        reschedule(&mut reminders, &[assessment(1, "2030-05-10")], &[60], due - 2 * HOUR);
+        take_due(&mut reminders, due - HOUR);
+
+        snooze(&mut reminders, "1:60", due + HOUR).unwrap();
+        assert!(take_due(&mut reminders, due).is_empty());
+
+        // No longer upcoming once it is due, but the snooze is kept
+        reschedule(&mut reminders, &[], &[60], due + HOUR);
+        let fired = take_due(&mut reminders, due + HOUR);
+        assert_eq!(fired.len(), 1);
+        assert_eq!(find(&reminders, "1:60").status, ReminderStatus::Fired);
+    }
+}
@@ -0,0 +1,497 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::db;
+use crate::seqta::{self, Assessment, Course, MessagesPayload, Notice, PastAssessmentsPayload};
+
+const DEFAULT_LIMIT: u32 = 50;
+/// Snippet highlight markers; control characters never appear in indexed text.
+const MARK_START: char = '\u{2}';
+const MARK_END: char = '\u{3}';
+
+/// One searchable item. `kind` and `key` identify it, so re-fetching something
+/// replaces its entry instead of adding another.
+struct Document {
+    kind: &'static str,
+    key: String,
+    title: String,
+    body: String,
+    subject: String,
+    date: String,
+    link: String,
+}
+
+/// Narrow a search down. Everything is optional.
+#[derive(Debug, Default, Deserialize)]
+pub struct SearchFilters {
+    /// Only these kinds: `notice`, `message`, `assessment`, `feedback` or `course`.
+    #[serde(default)]
+    pub kinds: Vec<String>,
+    /// Only items whose subject contains this, e.g. a subject code or sender.
+    #[serde(default)]
+    pub subject: Option<String>,
+    /// Earliest date, `YYYY-MM-DD`.
+    #[serde(default)]
+    pub from: Option<String>,
+    /// Latest date, `YYYY-MM-DD`.
+    #[serde(default)]
+    pub until: Option<String>,
+    #[serde(default)]
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    pub kind: String,
+    pub key: String,
+    /// HTML-escaped, with matches wrapped in `<mark>`.
+    pub title: String,
+    /// HTML-escaped excerpt around the best match, with matches wrapped in `<mark>`.
+    pub snippet: String,
+    /// Subject for assessments and courses, sender for messages, staff for notices.
+    pub subject: String,
+    pub date: String,
+    /// App route to open the item.
+    pub link: String,
+    /// Higher is more relevant.
+    pub score: f64,
+}
+
+/// Plain text from an HTML fragment, with tags dropped and whitespace collapsed.
+fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    text.replace([MARK_START, MARK_END], "").split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Prose from a SEQTA document, which is JSON of unknown shape. Only strings
+/// containing a space are kept, which skips ids, UUIDs and style names.
+fn document_text(value: &Value, out: &mut Vec<String>) {
+    match value {
+        Value::String(s) if s.trim().contains(' ') => out.push(html_to_text(s)),
+        Value::Array(items) => items.iter().for_each(|v| document_text(v, out)),
+        Value::Object(map) => map.values().for_each(|v| document_text(v, out)),
+        _ => {}
+    }
+}
+
+fn join_text(parts: impl IntoIterator<Item = String>) -> String {
+    parts.into_iter().filter(|p| !p.is_empty()).collect::<Vec<_>>().join("\n")
+}
+
+/// Insert `doc`, or update the existing entry for it. Empty fields keep what is already
+/// stored, so a list response (titles only) does not wipe a body fetched separately.
+/// FTS tables cannot be indexed by column, so entries are found through `search_keys`.
+fn upsert(conn: &Connection, doc: &Document) -> rusqlite::Result<()> {
+    let existing: Option<i64> = conn
+        .query_row(
+            "SELECT doc FROM search_keys WHERE kind = ?1 AND key = ?2",
+            params![doc.kind, doc.key],
+            |row| row.get(0),
+        )
+        .optional()?;
+    match existing {
+        Some(rowid) => {
+            conn.execute(
+                "UPDATE search_index SET
+                   title = COALESCE(NULLIF(?2, ''), title), body = COALESCE(NULLIF(?3, ''), body),
+                   subject = COALESCE(NULLIF(?4, ''), subject), date = COALESCE(NULLIF(?5, ''), date),
+                   link = COALESCE(NULLIF(?6, ''), link)
+                 WHERE rowid = ?1",
+                params![rowid, doc.title, doc.body, doc.subject, doc.date, doc.link],
+            )?;
+        }
+        None => {
+            conn.execute(
+                "INSERT INTO search_index (kind, key, title, body, subject, date, link)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![doc.kind, doc.key, doc.title, doc.body, doc.subject, doc.date, doc.link],
+            )?;
+            conn.execute(
+                "INSERT INTO search_keys (kind, key, doc) VALUES (?1, ?2, ?3)",
+                params![doc.kind, doc.key, conn.last_insert_rowid()],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Replace every entry of `kind` whose key is `prefix` or starts with `prefix:`.
+fn replace_group(kind: &str, prefix: &str, docs: &[Document]) -> Result<(), String> {
+    // `;` sorts straight after `:`, so this range is every key starting with `prefix:`
+    const GROUP: &str = "kind = ?1 AND (key = ?2 OR (key >= ?2 || ':' AND key < ?2 || ';'))";
+    db::with_db(|conn| {
+        let tx = conn.transaction()?;
+        tx.execute(
+            &format!("DELETE FROM search_index WHERE rowid IN (SELECT doc FROM search_keys WHERE {})", GROUP),
+            params![kind, prefix],
+        )?;
+        tx.execute(&format!("DELETE FROM search_keys WHERE {}", GROUP), params![kind, prefix])?;
+        for doc in docs {
+            upsert(&tx, doc)?;
+        }
+        tx.commit()
+    })
+}
+
+fn upsert_all(docs: &[Document]) -> Result<(), String> {
+    db::with_db(|conn| {
+        let tx = conn.transaction()?;
+        for doc in docs {
+            upsert(&tx, doc)?;
+        }
+        tx.commit()
+    })
+}
+
+fn assessment_doc(a: &Assessment) -> Document {
+    Document {
+        kind: "assessment",
+        key: a.id.to_string(),
+        title: a.title.clone(),
+        body: String::new(),
+        subject: format!("{} {}", a.subject, a.code).trim().to_string(),
+        date: a.due.clone(),
+        link: format!("/assessments/{}/{}", a.id, a.metaclass_id),
+    }
+}
+
+/// Description, criteria and feedback from `/assessment/get`.
+fn assessment_detail_docs(id: i64, metaclass: i64, payload: &Value) -> Vec<Document> {
+    let str_field = |v: &Value, name: &str| v.get(name).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+    let title = str_field(payload, "title");
+    let link = format!("/assessments/{}/{}", id, metaclass);
+
+    let mut criteria = Vec::new();
+    if let Some(c) = payload.get("criteria") {
+        document_text(c, &mut criteria);
+    }
+    let mut docs = vec![Document {
+        kind: "assessment",
+        key: id.to_string(),
+        title: title.clone(),
+        body: join_text(std::iter::once(html_to_text(&str_field(payload, "description"))).chain(criteria)),
+        subject: str_field(payload, "subject"),
+        date: str_field(payload, "due"),
+        link: link.clone(),
+    }];
+
+    let feedback = payload.get("engagement").map(|e| html_to_text(&str_field(e, "feedbackComment")));
+    if let Some(feedback) = feedback.filter(|f| !f.is_empty()) {
+        docs.push(Document {
+            kind: "feedback",
+            key: id.to_string(),
+            title,
+            body: feedback,
+            subject: str_field(payload, "subject"),
+            date: str_field(payload, "due"),
+            link,
+        });
+    }
+    docs
+}
+
+/// The course overview plus one entry per lesson, keyed `<programme>:<metaclass>[:<week>:<lesson>]`.
+fn course_docs(programme: i64, metaclass: i64, course: &Course) -> Vec<Document> {
+    let prefix = format!("{}:{}", programme, metaclass);
+    let subject = format!("{} {}", course.title, course.code).trim().to_string();
+    let link = format!("/courses?programme={}&metaclass={}", programme, metaclass);
+
+    let mut overview = Vec::new();
+    if let Some(document) = &course.document {
+        match serde_json::from_str::<Value>(document) {
+            Ok(value) => document_text(&value, &mut overview),
+            Err(_) => overview.push(html_to_text(document)),
+        }
+    }
+    let mut docs = vec![Document {
+        kind: "course",
+        key: prefix.clone(),
+        title: course.title.clone(),
+        body: join_text(overview.into_iter().chain(course.files.iter().map(|f| f.filename.clone()))),
+        subject: subject.clone(),
+        date: String::new(),
+        link: link.clone(),
+    }];
+
+    for (w, week) in course.weeks.iter().enumerate() {
+        let Some(lessons) = course.lessons.get(week.index) else { continue };
+        for (l, lesson) in lessons.iter().enumerate() {
+            let body = join_text(
+                [
+                    lesson.lesson.as_deref().map(html_to_text).unwrap_or_default(),
+                    lesson.homework.as_deref().map(html_to_text).unwrap_or_default(),
+                    lesson.document.as_ref().map(|d| html_to_text(&d.contents)).unwrap_or_default(),
+                ]
+                .into_iter()
+                .chain(lesson.resources.iter().map(|r| r.filename.clone())),
+            );
+            if body.is_empty() && lesson.title.is_empty() {
+                continue;
+            }
+            docs.push(Document {
+                kind: "course",
+                key: format!("{}:{}:{}", prefix, w, l),
+                title: if lesson.title.is_empty() {
+                    format!("Term {} Week {}", week.term, week.week)
+                } else {
+                    lesson.title.clone()
+                },
+                body,
+                subject: subject.clone(),
+                date: week.lessons.first().map(|t| t.date.clone()).unwrap_or_default(),
+                link: format!("{}&term={}&week={}", link, week.term, week.week),
+            });
+        }
+    }
+    docs
+}
+
+fn notice_docs(date: &str, notices: &[Notice]) -> Vec<Document> {
+    notices
+        .iter()
+        .enumerate()
+        .map(|(position, n)| Document {
+            kind: "notice",
+            key: format!("{}:{}", date, position),
+            title: n.title.clone(),
+            body: html_to_text(&n.contents),
+            subject: n.staff.clone(),
+            date: date.to_string(),
+            link: "/notices".to_string(),
+        })
+        .collect()
+}
+
+/// An integer field of a request body. The frontend sends some ids as strings, e.g.
+/// `programme` and `metaclass` for `/load/courses`.
+fn body_int(body: Option<&Value>, name: &str) -> Option<i64> {
+    match body?.get(name)? {
+        Value::String(s) => s.trim().parse().ok(),
+        v => v.as_i64(),
+    }
+}
+
+/// Add a successful SEQTA response to the search index, if it holds anything searchable.
+/// Called by netgrab for every network response, alongside `db::ingest`; failures are
+/// logged and ignored.
+pub(crate) fn ingest(path: &str, body: Option<&Value>, data: &str) {
+    let str_field = |name: &str| body.and_then(|b| b.get(name)).and_then(|v| v.as_str()).map(|s| s.to_string());
+    let int_field = |name: &str| body_int(body, name);
+
+    let result = if path.contains("/assessment/list/upcoming") {
+        seqta::decode_payload::<Vec<Assessment>>(path, data)
+            .map_err(|e| e.to_string())
+            .and_then(|a| upsert_all(&a.iter().map(assessment_doc).collect::<Vec<_>>()))
+    } else if path.contains("/assessment/list/past") {
+        seqta::decode_payload::<PastAssessmentsPayload>(path, data)
+            .map_err(|e| e.to_string())
+            .and_then(|p| upsert_all(&p.tasks.iter().map(assessment_doc).collect::<Vec<_>>()))
+    } else if path.ends_with("/assessment/get") {
+        match (int_field("assessment"), int_field("metaclass")) {
+            (Some(id), Some(metaclass)) => seqta::decode_payload::<Value>(path, data)
+                .map_err(|e| e.to_string())
+                .and_then(|p| upsert_all(&assessment_detail_docs(id, metaclass, &p))),
+            _ => Ok(()),
+        }
+    } else if path.contains("/load/courses") {
+        match (int_field("programme"), int_field("metaclass")) {
+            (Some(programme), Some(metaclass)) => seqta::decode_payload::<Course>(path, data)
+                .map_err(|e| e.to_string())
+                .and_then(|c| {
+                    replace_group("course", &format!("{}:{}", programme, metaclass), &course_docs(programme, metaclass, &c))
+                }),
+            _ => Ok(()),
+        }
+    } else if path.contains("/load/notices") {
+        match str_field("date") {
+            Some(date) => seqta::decode_payload::<Vec<Notice>>(path, data)
+                .map_err(|e| e.to_string())
+                .and_then(|n| replace_group("notice", &date, &notice_docs(&date, &n))),
+            None => Ok(()),
+        }
+    } else if path.ends_with("/load/message") {
+        match str_field("action").as_deref() {
+            Some("list") => seqta::decode_payload::<MessagesPayload>(path, data)
+                .map_err(|e| e.to_string())
+                .and_then(|p| {
+                    let docs: Vec<Document> = p
+                        .messages
+                        .iter()
+                        .map(|m| Document {
+                            kind: "message",
+                            key: m.id.to_string(),
+                            title: m.subject.clone(),
+                            body: String::new(),
+                            subject: m.sender.clone(),
+                            date: m.date.clone(),
+                            link: "/direqt-messages".to_string(),
+                        })
+                        .collect();
+                    upsert_all(&docs)
+                }),
+            Some("message") => match int_field("id") {
+                Some(id) => seqta::decode_payload::<Value>(path, data)
+                    .map_err(|e| e.to_string())
+                    .and_then(|p| {
+                        let field = |name: &str| p.get(name).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+                        upsert_all(&[Document {
+                            kind: "message",
+                            key: id.to_string(),
+                            title: field("subject"),
+                            body: html_to_text(&field("contents")),
+                            subject: field("sender"),
+                            date: field("date"),
+                            link: "/direqt-messages".to_string(),
+                        }])
+                    }),
+                None => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    } else {
+        Ok(())
+    };
+
+    if let Err(e) = result {
+        eprintln!("[Desqta] Failed to index {} for search: {}", path, e);
+    }
+}
+
+/// Turn what the user typed into an FTS5 query: every word must appear, and the last
+/// one may be a prefix so results show up while typing. Quoting each word keeps FTS5
+/// operators and punctuation in the input from being interpreted.
+fn fts_query(query: &str) -> Option<String> {
+    let words: Vec<String> = query
+        .split_whitespace()
+        .filter(|w| w.chars().any(|c| c.is_alphanumeric()))
+        .map(|w| format!("\"{}\"", w.replace('"', "\"\"")))
+        .collect();
+    if words.is_empty() {
+        return None;
+    }
+    Some(format!("{}*", words.join(" ")))
+}
+
+/// Escape text for HTML and turn the highlight markers into `<mark>` tags.
+fn render_highlight(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace(MARK_START, "<mark>")
+        .replace(MARK_END, "</mark>")
+}
+
+/// Full-text search over everything fetched from SEQTA so far: notices, DireqT messages,
+/// assessment descriptions and feedback, and course content. Results are ranked with
+/// titles weighted above subjects and bodies.
+#[tauri::command]
+pub fn search(query: String, filters: Option<SearchFilters>) -> Result<Vec<SearchHit>, String> {
+    let Some(fts) = fts_query(&query) else { return Ok(Vec::new()) };
+    let filters = filters.unwrap_or_default();
+    let kinds = if filters.kinds.is_empty() { String::new() } else { format!(",{},", filters.kinds.join(",")) };
+    let marks = (MARK_START.to_string(), MARK_END.to_string());
+
+    db::with_db(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT kind, key, highlight(search_index, 2, ?7, ?8), snippet(search_index, 3, ?7, ?8, '…', 16),
+                    subject, date, link, bm25(search_index, 0.0, 0.0, 10.0, 1.0, 3.0, 0.0, 0.0) AS rank
+             FROM search_index
+             WHERE search_index MATCH ?1
+               AND (?2 = '' OR instr(?2, ',' || kind || ',') > 0)
+               AND (?3 IS NULL OR subject LIKE '%' || ?3 || '%')
+               AND (?4 IS NULL OR substr(date, 1, 10) >= ?4)
+               AND (?5 IS NULL OR substr(date, 1, 10) <= ?5)
+             ORDER BY rank
+             LIMIT ?6",
+        )?;
+        let rows = stmt.query_map(
+            params![
+                fts,
+                kinds,
+                filters.subject,
+                filters.from,
+                filters.until,
+                filters.limit.unwrap_or(DEFAULT_LIMIT),
+                marks.0,
+                marks.1
+            ],
+            |row| {
+                let title: String = row.get(2)?;
+                let snippet: String = row.get(3)?;
+                let rank: f64 = row.get(7)?;
+                Ok(SearchHit {
+                    kind: row.get(0)?,
+                    key: row.get(1)?,
+                    title: render_highlight(&title),
+                    snippet: render_highlight(&snippet),
+                    subject: row.get(4)?,
+                    date: row.get(5)?,
+                    link: row.get(6)?,
+                    // bm25 is negative, lower being better
+                    score: -rank,
+                })
+            },
+        )?;
+        rows.collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn course_ids_may_be_strings() {
+        // As sent by the courses page
+        let body = json!({ "programme": "1234", "metaclass": "5678" });
+        assert_eq!(body_int(Some(&body), "programme"), Some(1234));
+        assert_eq!(body_int(Some(&body), "metaclass"), Some(5678));
+
+        let body = json!({ "assessment": 42, "metaclass": " 7 ", "date": "2025-03-01" });
+        assert_eq!(body_int(Some(&body), "assessment"), Some(42));
+        assert_eq!(body_int(Some(&body), "metaclass"), Some(7));
+        assert_eq!(body_int(Some(&body), "date"), None);
+        assert_eq!(body_int(Some(&body), "missing"), None);
+        assert_eq!(body_int(None, "programme"), None);
+    }
+
+    #[test]
+    fn query_matches_every_word_and_prefixes_the_last() {
+        assert_eq!(fts_query("maths essay").as_deref(), Some(r#""maths" "essay"*"#));
+        assert_eq!(fts_query(r#"say "hi"#).as_deref(), Some(r#""say" """hi"*"#));
+        // FTS5 operators are searched for as words, not obeyed
+        assert_eq!(fts_query("a OR b").as_deref(), Some(r#""a" "OR" "b"*"#));
+        assert_eq!(fts_query("  - * "), None);
+        assert_eq!(fts_query(""), None);
+    }
+
+    #[test]
+    fn html_becomes_plain_text() {
+        assert_eq!(html_to_text("<p>Bring a <b>calculator</b></p>"), "Bring a calculator");
+        assert_eq!(html_to_text("Fish&nbsp;&amp;&nbsp;chips &lt;3"), "Fish & chips <3");
+        assert_eq!(html_to_text("line<br>break\n\n  spaced"), "line break spaced");
+        assert_eq!(html_to_text(&format!("a{}b{}c", MARK_START, MARK_END)), "abc");
+    }
+}
@@ -0,0 +1,513 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::netgrab::{fetch_api_data, refresh_api_data, RequestMethod};
+use crate::neterror::NetError;
+
+/// SEQTA works out the student from the session cookie, but some endpoints still
+/// insist on a `student` field being present.
+const STUDENT_ID: i64 = 69;
+
+/// Every SEQTA student endpoint wraps its result as `{ "status": "200", "payload": ... }`.
+#[derive(Debug, Deserialize)]
+struct Envelope<T> {
+    #[serde(default)]
+    status: Option<String>,
+    payload: Option<T>,
+}
+
+/// POST `body` to a SEQTA student endpoint and decode its payload.
+/// Goes through `fetch_api_data`, so requests share its client, session cookies,
+/// response cache and retries.
+/// With `fresh`, the response cache is bypassed and network failures are returned as-is.
+async fn post<T: DeserializeOwned>(path: &str, body: Value, fresh: bool) -> Result<T, NetError> {
+    post_with_params(path, body, None, fresh).await
+}
+
+async fn post_with_params<T: DeserializeOwned>(
+    path: &str,
+    body: Value,
+    parameters: Option<HashMap<String, String>>,
+    fresh: bool,
+) -> Result<T, NetError> {
+    let text = if fresh {
+        refresh_api_data(path, RequestMethod::POST, Some(body), parameters).await?
+    } else {
+        fetch_api_data(path, RequestMethod::POST, None, Some(body), parameters, false, false).await?
+    };
+    decode_payload(path, &text)
+}
+
+/// Decode the payload of a raw response from `path`.
+pub(crate) fn decode_payload<T: DeserializeOwned>(path: &str, text: &str) -> Result<T, NetError> {
+    let envelope: Envelope<T> = serde_json::from_str(text)
+        .map_err(|e| NetError::decode(format!("Unexpected response from {}: {}", path, e)))?;
+    match envelope.payload {
+        Some(payload) => Ok(payload),
+        None => Err(NetError::decode(format!(
+            "{} returned no payload (status {})",
+            path,
+            envelope.status.as_deref().unwrap_or("unknown")
+        ))),
+    }
+}
+
+/// SEQTA flags are a mix of booleans, 0/1 and missing; treat anything truthy as true.
+fn truthy<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::Bool(b) => b,
+        Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0),
+        Value::String(s) => !s.is_empty() && s != "0" && s != "false",
+        Value::Array(a) => !a.is_empty(),
+        Value::Object(_) => true,
+        Value::Null => false,
+    })
+}
+
+/// Some fields arrive as a number from one school and a string from another.
+fn lenient_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::String(s) => s,
+        Value::Null => String::new(),
+        Value::Array(a) => a
+            .iter()
+            .map(|v| v.as_str().map(|s| s.to_string()).unwrap_or_else(|| v.to_string()))
+            .collect::<Vec<_>>()
+            .join(", "),
+        other => other.to_string(),
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TimetableLesson {
+    /// Start time, `HH:MM:SS`.
+    pub from: String,
+    /// End time, `HH:MM:SS`.
+    pub until: String,
+    /// `YYYY-MM-DD`
+    pub date: String,
+    pub code: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub staff: Option<String>,
+    #[serde(default)]
+    pub room: Option<String>,
+    #[serde(default)]
+    pub period: Option<String>,
+    #[serde(default)]
+    pub metaclass: Option<i64>,
+    #[serde(default)]
+    pub programme: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct TimetablePayload {
+    #[serde(default)]
+    pub items: Vec<TimetableLesson>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Assessment {
+    pub id: i64,
+    pub title: String,
+    #[serde(default)]
+    pub subject: String,
+    #[serde(default)]
+    pub code: String,
+    /// Due date as sent by SEQTA, `YYYY-MM-DD` or an ISO timestamp.
+    #[serde(default)]
+    pub due: String,
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default, rename(deserialize = "metaclassID"))]
+    pub metaclass_id: i64,
+    #[serde(default, rename(deserialize = "programmeID"))]
+    pub programme_id: i64,
+    #[serde(default)]
+    pub colour: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct PastAssessmentsPayload {
+    #[serde(default)]
+    pub tasks: Vec<Assessment>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Subject {
+    pub code: String,
+    #[serde(default)]
+    pub classunit: i64,
+    #[serde(default)]
+    pub description: String,
+    pub metaclass: i64,
+    #[serde(default)]
+    pub title: String,
+    pub programme: i64,
+    #[serde(default)]
+    pub marksbook_type: Option<String>,
+}
+
+/// A group of subjects, usually one per year/semester.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SubjectFolder {
+    pub id: i64,
+    #[serde(default)]
+    pub code: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default, deserialize_with = "truthy")]
+    pub active: bool,
+    #[serde(default)]
+    pub subjects: Vec<Subject>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Notice {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub title: String,
+    #[serde(default)]
+    pub label_title: String,
+    #[serde(default)]
+    pub staff: String,
+    #[serde(default)]
+    pub colour: Option<String>,
+    #[serde(default)]
+    pub label: Option<i64>,
+    /// HTML body.
+    #[serde(default)]
+    pub contents: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NoticeLabel {
+    pub id: i64,
+    pub title: String,
+    #[serde(default)]
+    pub colour: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CourseFile {
+    pub id: i64,
+    pub uuid: String,
+    pub filename: String,
+    #[serde(default)]
+    pub mimetype: String,
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub size: String,
+    #[serde(default)]
+    pub created_date: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CourseLessonTime {
+    #[serde(rename(deserialize = "p"), default)]
+    pub period: String,
+    #[serde(rename(deserialize = "s"), default)]
+    pub start: String,
+    #[serde(rename(deserialize = "e"), default)]
+    pub end: String,
+    #[serde(rename(deserialize = "d"), default)]
+    pub date: String,
+}
+
+/// One week of a course's term schedule.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CourseWeek {
+    #[serde(rename(deserialize = "t"))]
+    pub term: i64,
+    #[serde(rename(deserialize = "w"))]
+    pub week: i64,
+    #[serde(rename(deserialize = "l"), default)]
+    pub lessons: Vec<CourseLessonTime>,
+    /// Index into `Course::lessons` for this week's content.
+    #[serde(rename(deserialize = "n"))]
+    pub index: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LessonResource {
+    pub uuid: String,
+    pub filename: String,
+    #[serde(default)]
+    pub mimetype: String,
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub size: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LessonDocument {
+    pub id: i64,
+    /// HTML body.
+    #[serde(default)]
+    pub contents: String,
+}
+
+/// Content posted for a single lesson.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LessonContent {
+    #[serde(rename(deserialize = "t"), default)]
+    pub title: String,
+    #[serde(rename(deserialize = "h"), default)]
+    pub homework: Option<String>,
+    #[serde(rename(deserialize = "l"), default)]
+    pub lesson: Option<String>,
+    #[serde(default)]
+    pub document: Option<LessonDocument>,
+    #[serde(rename(deserialize = "r"), default)]
+    pub resources: Vec<LessonResource>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Course {
+    #[serde(rename(deserialize = "u"), default)]
+    pub id: String,
+    #[serde(rename(deserialize = "c"), default)]
+    pub code: String,
+    #[serde(rename(deserialize = "t"), default)]
+    pub title: String,
+    /// UUID of the course banner image.
+    #[serde(rename(deserialize = "im"), default)]
+    pub image: Option<String>,
+    /// Course overview, a JSON-encoded SEQTA document.
+    #[serde(default)]
+    pub document: Option<String>,
+    #[serde(rename(deserialize = "cf"), default)]
+    pub files: Vec<CourseFile>,
+    #[serde(rename(deserialize = "d"), default)]
+    pub weeks: Vec<CourseWeek>,
+    /// Lesson content, indexed by `CourseWeek::index` then lesson.
+    #[serde(rename(deserialize = "w"), default)]
+    pub lessons: Vec<Vec<LessonContent>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Report {
+    pub uuid: String,
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub year: String,
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub terms: String,
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub types: String,
+    #[serde(default)]
+    pub created_date: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MessageParticipant {
+    #[serde(default)]
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MessageSummary {
+    pub id: i64,
+    #[serde(default)]
+    pub subject: String,
+    #[serde(default)]
+    pub sender: String,
+    #[serde(default)]
+    pub date: String,
+    #[serde(default, deserialize_with = "truthy")]
+    pub read: bool,
+    #[serde(default, deserialize_with = "truthy")]
+    pub attachments: bool,
+    #[serde(default)]
+    pub participants: Vec<MessageParticipant>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct MessagesPayload {
+    #[serde(default)]
+    pub messages: Vec<MessageSummary>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HomeworkItem {
+    pub meta: i64,
+    pub id: i64,
+    pub title: String,
+    pub items: Vec<String>,
+}
+
+/// A file stored in SEQTA by `/seqta/student/file/upload/xhr2`, ready to be linked to a submission.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UploadedFile {
+    pub id: i64,
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub uuid: String,
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub filename: String,
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub mimetype: String,
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub size: String,
+}
+
+/// A file attached to an assessment submission, by the student or by a teacher.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SubmissionFile {
+    #[serde(default)]
+    pub id: i64,
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub uuid: String,
+    /// Some schools send `filename`, others `name`.
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub filename: String,
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub name: String,
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub mimetype: String,
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub size: String,
+    #[serde(default)]
+    pub created_date: Option<String>,
+    #[serde(default, deserialize_with = "truthy")]
+    pub staff: bool,
+}
+
+pub(crate) async fn timetable(from: &str, until: &str, fresh: bool) -> Result<Vec<TimetableLesson>, NetError> {
+    let payload: TimetablePayload = post(
+        "/seqta/student/load/timetable",
+        json!({ "from": from, "until": until, "student": STUDENT_ID }),
+        fresh,
+    )
+    .await?;
+    Ok(payload.items)
+}
+
+pub(crate) async fn upcoming_assessments(fresh: bool) -> Result<Vec<Assessment>, NetError> {
+    post("/seqta/student/assessment/list/upcoming", json!({ "student": STUDENT_ID }), fresh).await
+}
+
+pub(crate) async fn past_assessments(programme: i64, metaclass: i64, fresh: bool) -> Result<Vec<Assessment>, NetError> {
+    let payload: PastAssessmentsPayload = post(
+        "/seqta/student/assessment/list/past",
+        json!({ "programme": programme, "metaclass": metaclass, "student": STUDENT_ID }),
+        fresh,
+    )
+    .await?;
+    Ok(payload.tasks)
+}
+
+pub(crate) async fn notices(date: &str, fresh: bool) -> Result<Vec<Notice>, NetError> {
+    post("/seqta/student/load/notices", json!({ "date": date }), fresh).await
+}
+
+pub(crate) async fn messages(label: &str, offset: u32, limit: u32, fresh: bool) -> Result<Vec<MessageSummary>, NetError> {
+    let payload: MessagesPayload = post(
+        "/seqta/student/load/message",
+        json!({
+            "searchValue": "",
+            "sortBy": "date",
+            "sortOrder": "desc",
+            "action": "list",
+            "label": label,
+            "offset": offset,
+            "limit": limit,
+            "datetimeUntil": null,
+        }),
+        fresh,
+    )
+    .await?;
+    Ok(payload.messages)
+}
+
+/// Lessons between `from` and `until` inclusive, both `YYYY-MM-DD`.
+#[tauri::command]
+pub async fn get_timetable(from: String, until: String) -> Result<Vec<TimetableLesson>, NetError> {
+    timetable(&from, &until, false).await
+}
+
+#[tauri::command]
+pub async fn get_upcoming_assessments() -> Result<Vec<Assessment>, NetError> {
+    upcoming_assessments(false).await
+}
+
+/// Assessments for one subject that are past their due date.
+#[tauri::command]
+pub async fn get_past_assessments(programme: i64, metaclass: i64) -> Result<Vec<Assessment>, NetError> {
+    past_assessments(programme, metaclass, false).await
+}
+
+#[tauri::command]
+pub async fn get_subjects() -> Result<Vec<SubjectFolder>, NetError> {
+    post("/seqta/student/load/subjects", json!({}), false).await
+}
+
+/// Notices posted for `date` (`YYYY-MM-DD`).
+#[tauri::command]
+pub async fn get_notices(date: String) -> Result<Vec<Notice>, NetError> {
+    notices(&date, false).await
+}
+
+#[tauri::command]
+pub async fn get_notice_labels() -> Result<Vec<NoticeLabel>, NetError> {
+    post("/seqta/student/load/notices", json!({ "mode": "labels" }), false).await
+}
+
+#[tauri::command]
+pub async fn get_course(programme: i64, metaclass: i64) -> Result<Course, NetError> {
+    // SEQTA only accepts these as strings here
+    post(
+        "/seqta/student/load/courses",
+        json!({ "programme": programme.to_string(), "metaclass": metaclass.to_string() }),
+        false,
+    )
+    .await
+}
+
+#[tauri::command]
+pub async fn get_reports() -> Result<Vec<Report>, NetError> {
+    reports(false).await
+}
+
+/// One page of a DireqtMessages folder (`inbox`, `sent` or `outbox`), newest first.
+#[tauri::command]
+pub async fn get_messages(label: String, offset: u32, limit: u32) -> Result<Vec<MessageSummary>, NetError> {
+    messages(&label, offset, limit, false).await
+}
+
+pub(crate) async fn reports(fresh: bool) -> Result<Vec<Report>, NetError> {
+    post("/seqta/student/load/reports", json!({}), fresh).await
+}
+
+pub(crate) async fn submissions(assessment: i64, metaclass: i64, fresh: bool) -> Result<Vec<SubmissionFile>, NetError> {
+    post(
+        "/seqta/student/assessment/submissions/get",
+        json!({ "assessment": assessment, "metaclass": metaclass, "student": STUDENT_ID }),
+        fresh,
+    )
+    .await
+}
+
+/// Attach uploaded files to the student's submission for an assessment.
+pub(crate) async fn link_submission_files(assessment: i64, metaclass: i64, file_ids: &[i64]) -> Result<(), NetError> {
+    let path = "/seqta/student/assessment/submissions/save";
+    let body = json!({ "action": "link", "assID": assessment, "metaclass": metaclass, "files": file_ids });
+    let text = fetch_api_data(path, RequestMethod::POST, None, Some(body), None, false, false).await?;
+    let envelope: Envelope<Value> = serde_json::from_str(&text)
+        .map_err(|e| NetError::decode(format!("Unexpected response from {}: {}", path, e)))?;
+    match envelope.status.as_deref() {
+        Some("200") => Ok(()),
+        status => Err(NetError::http(status.and_then(|s| s.parse().ok()).unwrap_or(500), text)),
+    }
+}
+
+#[tauri::command]
+pub async fn get_submissions(assessment: i64, metaclass: i64) -> Result<Vec<SubmissionFile>, NetError> {
+    submissions(assessment, metaclass, false).await
+}
+
+#[tauri::command]
+pub async fn get_homework() -> Result<Vec<HomeworkItem>, NetError> {
+    let mut params = HashMap::new();
+    params.insert(String::from("majhvjju"), String::new());
+    post_with_params("/seqta/student/dashlet/summary/homework", json!({}), Some(params), false).await
+}
@@ -1,29 +1,104 @@
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use std::{
     fs,
-    io::{self, Read},
+    io,
     path::PathBuf,
+    sync::{Mutex, RwLock},
 };
 
-/// Location: `$DATA_DIR/DesQTA/session.json`
-#[allow(dead_code)]
-pub fn session_file() -> PathBuf {
+use crate::calendar_feed;
+use crate::crypto;
+use crate::login;
+use crate::settings::Settings;
+
+/// Files that lived directly in `$DATA_DIR/DesQTA` before profiles existed and now
+/// belong to a profile.
+const PROFILE_SCOPED_FILES: &[&str] = &["settings.json", "analytics.json", "cache"];
+
+/// Held for every read-modify-write of `profiles.json`, so background tasks saving at the
+/// same time cannot undo each other's changes.
+static PROFILES_LOCK: Mutex<()> = Mutex::new(());
+
+/// The active profile's id, once known, so `profile_dir` does not decrypt `profiles.json`
+/// on every call. `Profiles::save` keeps it current through switches, logins and logouts.
+static ACTIVE_ID: RwLock<Option<Option<String>>> = RwLock::new(None);
+
+fn active_id() -> Option<String> {
+    if let Some(id) = ACTIVE_ID.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        return id.clone();
+    }
+    let mut cached = ACTIVE_ID.write().unwrap_or_else(|e| e.into_inner());
+    if let Some(id) = cached.as_ref() {
+        return id.clone();
+    }
+    // Not cached if unreadable (e.g. the keyring is still locked), so it is tried again
+    let active = Profiles::read()?.active;
+    *cached = Some(active.clone());
+    active
+}
+
+fn data_dir() -> PathBuf {
     // e.g. %APPDATA%/DesQTA on Windows, ~/.local/share/DesQTA on Linux/macOS
     let mut dir = dirs_next::data_dir().expect("Unable to determine data dir");
     dir.push("DesQTA");
     if !dir.exists() {
         fs::create_dir_all(&dir).expect("Unable to create data dir");
     }
+    dir
+}
+
+/// Location: `$DATA_DIR/DesQTA/session.json`
+/// Only read to migrate installs from before profiles existed.
+fn session_file() -> PathBuf {
+    let mut dir = data_dir();
     dir.push("session.json");
     dir
 }
 
+/// Location: `$DATA_DIR/DesQTA/profiles.json`
+fn profiles_file() -> PathBuf {
+    let mut dir = data_dir();
+    dir.push("profiles.json");
+    dir
+}
+
+fn profile_dir_for(id: &str) -> PathBuf {
+    let mut dir = data_dir();
+    dir.push("profiles");
+    dir.push(id);
+    dir
+}
+
+/// Directory for data belonging to the active profile (settings, analytics, caches).
+/// Location: `$DATA_DIR/DesQTA/profiles/<id>`, or `$DATA_DIR/DesQTA` if no profile exists yet.
+pub fn profile_dir() -> PathBuf {
+    let dir = match active_id() {
+        Some(id) => profile_dir_for(&id),
+        None => data_dir(),
+    };
+    if !dir.exists() {
+        fs::create_dir_all(&dir).expect("Unable to create profile dir");
+    }
+    dir
+}
+
+fn now_secs() -> i64 {
+    time::OffsetDateTime::now_utc().unix_timestamp()
+}
+
 /// Saved session state.
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Session {
     pub base_url: String,
     pub jsessionid: String,
     pub additional_cookies: Vec<Cookie>,
+    /// Unix timestamp (seconds) at which the JSESSIONID cookie expires, if known.
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+    /// Set once SEQTA has rejected the cookie; cleared by the next successful login.
+    #[serde(default)]
+    pub expired: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -34,44 +109,381 @@ pub struct Cookie {
     pub path: Option<String>,
 }
 
-#[allow(dead_code)]
-impl Session {
-    /// Load from disk; returns empty/default if none.
+/// A saved SEQTA login: one student at one school.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Profile {
+    pub id: String,
+    pub display_name: String,
+    /// Unix timestamp (seconds) of the last time this profile was made active.
+    pub last_used: i64,
+    pub session: Session,
+}
+
+/// Every saved profile and which one is in use.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Profiles {
+    pub active: Option<String>,
+    pub profiles: Vec<Profile>,
+}
+
+/// What the frontend is told about a profile; cookies never leave the backend.
+#[derive(Debug, Serialize, Clone)]
+pub struct ProfileSummary {
+    pub id: String,
+    pub display_name: String,
+    pub base_url: String,
+    pub last_used: i64,
+    pub active: bool,
+    pub logged_in: bool,
+}
+
+impl Profiles {
+    /// Load from disk; returns empty if none.
     pub fn load() -> Self {
-        let path = session_file();
-        if let Ok(mut file) = fs::File::open(path) {
-            let mut contents = String::new();
-            if file.read_to_string(&mut contents).is_ok() {
-                if let Ok(sess) = serde_json::from_str::<Session>(&contents) {
-                    return sess;
+        Self::read().unwrap_or_default()
+    }
+
+    /// Load from disk, or `None` if the file exists but cannot be read.
+    fn read() -> Option<Self> {
+        let path = profiles_file();
+        if !path.exists() {
+            return Some(Profiles::default());
+        }
+        let contents = crypto::read_encrypted(&path)?;
+        serde_json::from_str::<Profiles>(&contents).ok()
+    }
+
+    /// Persist to disk, encrypted. Written to a temporary file first so a concurrent
+    /// `load` never sees a half-written file.
+    fn save(&self) -> io::Result<()> {
+        let mut cached = ACTIVE_ID.write().unwrap_or_else(|e| e.into_inner());
+        crypto::write_encrypted(&profiles_file(), &serde_json::to_string(self).unwrap())?;
+        *cached = Some(self.active.clone());
+        Ok(())
+    }
+
+    /// Load, change and save the profiles while holding `PROFILES_LOCK`.
+    fn update<T>(f: impl FnOnce(&mut Profiles) -> T) -> io::Result<T> {
+        let _guard = PROFILES_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        // Saving over a file that could not be read would wipe every profile
+        let mut profiles = Self::read().ok_or_else(|| io::Error::other("profiles.json cannot be read"))?;
+        let result = f(&mut profiles);
+        profiles.save()?;
+        Ok(result)
+    }
+
+    /// Turn a `session.json` from before profiles existed into the first profile. Called
+    /// once from `setup`, before any background task reads the session.
+    pub fn migrate_legacy_session() {
+        let _guard = PROFILES_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        if profiles_file().exists() {
+            return;
+        }
+        Self::migrate_legacy();
+    }
+
+    fn migrate_legacy() -> Option<Self> {
+        let legacy = session_file();
+        let contents = crypto::read_encrypted(&legacy)?;
+        let session = serde_json::from_str::<Session>(&contents).ok()?;
+
+        let mut profiles = Profiles::default();
+        let id = profiles.insert(session);
+        let dir = profile_dir_for(&id);
+        if let Err(e) = fs::create_dir_all(&dir) {
+            eprintln!("[Desqta] Failed to create profile dir: {}", e);
+            return None;
+        }
+        for name in PROFILE_SCOPED_FILES {
+            let from = data_dir().join(name);
+            if from.exists() {
+                if let Err(e) = fs::rename(&from, dir.join(name)) {
+                    eprintln!("[Desqta] Failed to move {} into profile: {}", name, e);
+                }
+            }
+        }
+
+        if let Err(e) = profiles.save() {
+            eprintln!("[Desqta] Failed to save migrated profile: {}", e);
+            return None;
+        }
+        let _ = fs::remove_file(legacy);
+        println!("[Desqta] Migrated existing session into profile {}", id);
+        Some(profiles)
+    }
+
+    pub fn active_profile(&self) -> Option<&Profile> {
+        let id = self.active.as_ref()?;
+        self.profiles.iter().find(|p| &p.id == id)
+    }
+
+    fn active_profile_mut(&mut self) -> Option<&mut Profile> {
+        let id = self.active.clone()?;
+        self.profiles.iter_mut().find(|p| p.id == id)
+    }
+
+    /// Add `session` as a new profile and make it active. Returns the new profile's id.
+    fn insert(&mut self, session: Session) -> String {
+        let mut bytes = [0u8; 8];
+        rand::rngs::OsRng.fill_bytes(&mut bytes);
+        let id: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+
+        let display_name = url::Url::parse(&session.base_url)
+            .ok()
+            .and_then(|u| u.host_str().map(|h| h.to_string()))
+            .unwrap_or_else(|| session.base_url.clone());
+
+        self.profiles.push(Profile {
+            id: id.clone(),
+            display_name,
+            last_used: now_secs(),
+            session,
+        });
+        self.active = Some(id.clone());
+        id
+    }
+
+    /// Store a freshly logged-in session.
+    /// If the active profile is for the same school and was logged out or expired, it is
+    /// logged back in; otherwise a new profile is added and made active.
+    pub fn add_login(session: Session) -> io::Result<String> {
+        let (id, seed_settings) = Self::update(|profiles| {
+            if let Some(active) = profiles.active_profile_mut() {
+                let logged_out = active.session.jsessionid.is_empty() || active.session.is_expired();
+                if logged_out && active.session.base_url == session.base_url {
+                    active.session = session;
+                    active.last_used = now_secs();
+                    return (active.id.clone(), None);
                 }
             }
+            let seed_settings = match &profiles.active {
+                Some(id) => profile_dir_for(id),
+                None => data_dir(),
+            }
+            .join("settings.json");
+            (profiles.insert(session), Some(seed_settings))
+        })?;
+        let Some(seed_settings) = seed_settings else {
+            return Ok(id);
+        };
+
+        // Start the new profile with the same preferences as the one the user came from
+        let dir = profile_dir_for(&id);
+        fs::create_dir_all(&dir)?;
+        if seed_settings.exists() {
+            let _ = fs::copy(seed_settings, dir.join("settings.json"));
         }
-        Session {
-            base_url: String::new(),
-            jsessionid: String::new(),
-            additional_cookies: Vec::new(),
+
+        println!("[Desqta] Added profile {}", id);
+        Ok(id)
+    }
+
+    pub fn summaries(&self) -> Vec<ProfileSummary> {
+        let mut summaries: Vec<ProfileSummary> = self
+            .profiles
+            .iter()
+            .map(|p| ProfileSummary {
+                id: p.id.clone(),
+                display_name: p.display_name.clone(),
+                base_url: p.session.base_url.clone(),
+                last_used: p.last_used,
+                active: self.active.as_deref() == Some(p.id.as_str()),
+                logged_in: !p.session.jsessionid.is_empty() && !p.session.is_expired(),
+            })
+            .collect();
+        summaries.sort_by_key(|s| std::cmp::Reverse(s.last_used));
+        summaries
+    }
+
+    /// Make `id` the active profile.
+    pub fn switch_to(id: &str) -> Result<(), String> {
+        Self::update(|profiles| {
+            let profile = profiles
+                .profiles
+                .iter_mut()
+                .find(|p| p.id == id)
+                .ok_or_else(|| format!("No profile with id {}", id))?;
+            profile.last_used = now_secs();
+            profiles.active = Some(id.to_string());
+            Ok(())
+        })
+        .map_err(|e| e.to_string())?
+    }
+
+    /// Delete `id` and its data. If it was active, the most recently used remaining
+    /// profile takes over. Returns whether it was active.
+    pub fn remove(id: &str) -> Result<bool, String> {
+        let was_active = Self::update(|profiles| {
+            let before = profiles.profiles.len();
+            profiles.profiles.retain(|p| p.id != id);
+            if profiles.profiles.len() == before {
+                return Err(format!("No profile with id {}", id));
+            }
+
+            let was_active = profiles.active.as_deref() == Some(id);
+            if was_active {
+                profiles.active = profiles
+                    .profiles
+                    .iter()
+                    .max_by_key(|p| p.last_used)
+                    .map(|p| p.id.clone());
+            }
+            Ok(was_active)
+        })
+        .map_err(|e| e.to_string())??;
+
+        let dir = profile_dir_for(id);
+        if dir.exists() {
+            // Only the active profile's database is open, and Windows will not delete it while it is
+            if was_active {
+                crate::db::close();
+            }
+            fs::remove_dir_all(dir).map_err(|e| e.to_string())?;
         }
+        Ok(was_active)
     }
 
-    /// Persist to disk.
-    pub fn save(&self) -> io::Result<()> {
-        let path = session_file();
-        fs::write(path, serde_json::to_string(self).unwrap())
+    /// Rename `id`, or the active profile if `None`.
+    pub fn rename(id: Option<&str>, display_name: String) -> Result<(), String> {
+        Self::update(|profiles| {
+            let id = match id {
+                Some(id) => id.to_string(),
+                None => profiles.active.clone().ok_or("No active profile")?,
+            };
+            let profile = profiles
+                .profiles
+                .iter_mut()
+                .find(|p| p.id == id)
+                .ok_or_else(|| format!("No profile with id {}", id))?;
+            profile.display_name = display_name;
+            Ok(())
+        })
+        .map_err(|e| e.to_string())?
     }
+}
 
-    /// True if both URL and cookie are present.
+impl Session {
+    /// Load the active profile's session; returns empty/default if none.
+    pub fn load() -> Self {
+        Self::load_active().1
+    }
+
+    /// The active profile's id along with its session. Requests keep the id so what they
+    /// learn about the session is recorded against the profile they were made for.
+    pub fn load_active() -> (Option<String>, Self) {
+        Profiles::load()
+            .active_profile()
+            .map(|p| (Some(p.id.clone()), p.session.clone()))
+            .unwrap_or_default()
+    }
+
+    /// True if both URL and cookie are present and the cookie is not known to be dead.
     pub fn exists() -> bool {
         let s = Self::load();
-        !(s.base_url.is_empty() || s.jsessionid.is_empty())
+        !s.base_url.is_empty() && !s.jsessionid.is_empty() && !s.is_expired()
     }
 
-    /// Clear the session data and remove the file
-    pub fn clear_file() -> io::Result<()> {
-        let path = session_file();
-        if path.exists() {
-            fs::remove_file(path)?;
+    /// True if SEQTA has rejected the cookie or its expiry time has passed.
+    pub fn is_expired(&self) -> bool {
+        if self.expired {
+            return true;
         }
-        Ok(())
+        match self.expires_at {
+            Some(expires_at) => expires_at <= time::OffsetDateTime::now_utc().unix_timestamp(),
+            None => false,
+        }
+    }
+
+    /// Change the session of profile `profile_id`, whether or not it is still active.
+    /// `f` returns whether it changed anything.
+    fn update_profile(profile_id: Option<&str>, f: impl FnOnce(&mut Session) -> bool) -> io::Result<bool> {
+        let Some(id) = profile_id else {
+            return Ok(false);
+        };
+        Profiles::update(|profiles| {
+            profiles
+                .profiles
+                .iter_mut()
+                .find(|p| p.id == id)
+                .is_some_and(|p| f(&mut p.session))
+        })
+    }
+
+    /// Flag the session of `profile_id` as rejected by SEQTA.
+    /// Returns true if the session was valid until now.
+    pub fn mark_expired(profile_id: Option<&str>) -> bool {
+        let result = Self::update_profile(profile_id, |s| {
+            if s.jsessionid.is_empty() || s.expired {
+                return false;
+            }
+            s.expired = true;
+            true
+        });
+        result.unwrap_or_else(|e| {
+            eprintln!("[Desqta] Failed to mark session as expired: {}", e);
+            false
+        })
     }
+
+    /// Clear the expired flag of `profile_id` after SEQTA has accepted the cookie again.
+    pub fn mark_valid(profile_id: Option<&str>) {
+        let expired = Profiles::load()
+            .profiles
+            .iter()
+            .any(|p| Some(p.id.as_str()) == profile_id && p.session.expired);
+        if !expired {
+            return;
+        }
+        let result = Self::update_profile(profile_id, |s| {
+            s.expired = false;
+            true
+        });
+        if let Err(e) = result {
+            eprintln!("[Desqta] Failed to update session: {}", e);
+        }
+    }
+
+    /// Log the active profile out. The profile and its settings are kept so the
+    /// next login to the same school picks them back up.
+    pub fn clear() -> io::Result<()> {
+        Profiles::update(|profiles| {
+            if let Some(profile) = profiles.active_profile_mut() {
+                profile.session.jsessionid.clear();
+                profile.session.additional_cookies.clear();
+                profile.session.expires_at = None;
+                profile.session.expired = false;
+            }
+        })
+    }
+}
+
+/// Every saved profile, most recently used first.
+#[tauri::command]
+pub fn list_profiles() -> Vec<ProfileSummary> {
+    Profiles::load().summaries()
+}
+
+/// Make another profile active and reload the frontend against it.
+#[tauri::command]
+pub fn switch_profile(app: tauri::AppHandle, id: String) -> Result<(), String> {
+    Profiles::switch_to(&id)?;
+    println!("[Desqta] Switched to profile {}", id);
+    calendar_feed::apply(&Settings::load());
+    login::force_reload(app);
+    Ok(())
+}
+
+/// Delete a profile along with its settings, analytics and cached data.
+#[tauri::command]
+pub fn remove_profile(app: tauri::AppHandle, id: String) -> Result<(), String> {
+    if Profiles::remove(&id)? {
+        login::force_reload(app);
+    }
+    Ok(())
+}
+
+/// Rename a profile; renames the active one if `id` is omitted.
+#[tauri::command]
+pub fn rename_profile(id: Option<String>, display_name: String) -> Result<(), String> {
+    Profiles::rename(id.as_deref(), display_name)
 }
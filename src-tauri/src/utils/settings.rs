@@ -1,22 +1,19 @@
 use serde::{Deserialize, Serialize};
 use std::{
     fs,
-    io::{self, Read},
-    path::PathBuf,
+    io,
+    path::{Path, PathBuf},
 };
-use reqwest;
-use serde_json;
+use serde_json::{self, json, Map, Value};
 
-#[path = "session.rs"]
-mod session;
+use crate::calendar_feed;
+use crate::crypto;
+use crate::neterror::NetError;
+use crate::session;
 
-/// Location: `$DATA_DIR/DesQTA/settings.json`
+/// Location: `$DATA_DIR/DesQTA/profiles/<id>/settings.json`
 fn settings_file() -> PathBuf {
-    let mut dir = dirs_next::data_dir().expect("Unable to determine data dir");
-    dir.push("DesQTA");
-    if !dir.exists() {
-        fs::create_dir_all(&dir).expect("Unable to create data dir");
-    }
+    let mut dir = session::profile_dir();
     dir.push("settings.json");
     dir
 }
@@ -38,21 +35,19 @@ pub struct CloudToken {
 }
 
 impl CloudToken {
+    /// Load from disk; the file is encrypted at rest and plaintext files are migrated.
     pub fn load() -> Self {
         let path = cloud_token_file();
-        if let Ok(mut file) = fs::File::open(path) {
-            let mut contents = String::new();
-            if file.read_to_string(&mut contents).is_ok() {
-                if let Ok(tok) = serde_json::from_str::<CloudToken>(&contents) {
-                    return tok;
-                }
+        if let Some(contents) = crypto::read_encrypted(&path) {
+            if let Ok(tok) = serde_json::from_str::<CloudToken>(&contents) {
+                return tok;
             }
         }
         CloudToken::default()
     }
     pub fn save(&self) -> io::Result<()> {
         let path = cloud_token_file();
-        fs::write(path, serde_json::to_string(self).unwrap())
+        crypto::write_encrypted(&path, &serde_json::to_string(self).unwrap())
     }
     pub fn clear_file() -> io::Result<()> {
         let path = cloud_token_file();
@@ -63,8 +58,15 @@ impl CloudToken {
     }
 }
 
+/// Schema version written by this build. Bump it and append to `MIGRATIONS` whenever a
+/// field is renamed, removed or changes shape; new fields only need a default.
+pub const SETTINGS_VERSION: u32 = 2;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct Settings {
+    /// Schema version the settings were stored with.
+    pub version: u32,
     pub shortcuts: Vec<Shortcut>,
     pub feeds: Vec<Feed>,
     pub weather_enabled: bool,
@@ -86,11 +88,51 @@ pub struct Settings {
     pub current_theme: Option<String>,
     pub widget_layout: Vec<WidgetLayout>,
     pub dev_sensitive_info_hider: bool,
+    /// How many times an idempotent SEQTA request is retried after a transient failure.
+    pub request_max_retries: u32,
+    /// Base delay for exponential backoff between retries, in milliseconds.
+    pub request_retry_base_delay_ms: u64,
+    /// Seconds between background heartbeats that keep the SEQTA session alive (0 disables them).
+    pub heartbeat_interval_secs: u64,
+    /// Refresh SEQTA data in the background, including while the window is hidden to the tray.
+    pub sync_enabled: bool,
+    /// Minutes between background timetable refreshes.
+    pub sync_timetable_interval_mins: u64,
+    /// Minutes between background refreshes of upcoming assessments.
+    pub sync_assessments_interval_mins: u64,
+    /// Minutes between background notice refreshes.
+    pub sync_notices_interval_mins: u64,
+    /// Minutes between background inbox refreshes.
+    pub sync_messages_interval_mins: u64,
+    /// Show a desktop notification when a new assessment is set.
+    pub notify_new_assessments: bool,
+    /// Show a desktop notification when marks are released for an assessment.
+    pub notify_marks_released: bool,
+    /// Show a desktop notification for new notices.
+    pub notify_notices: bool,
+    /// Show a desktop notification for new direqt messages.
+    pub notify_messages: bool,
+    /// How long before an assessment is due to remind about it, in minutes.
+    pub reminder_lead_times_mins: Vec<u64>,
+    /// Serve the timetable and assessments as subscribable calendars on localhost.
+    pub calendar_feed_enabled: bool,
+    /// Port of the local calendar feed server.
+    pub calendar_feed_port: u16,
+    /// Minutes between checks for newly published reports.
+    pub sync_reports_interval_mins: u64,
+    /// Notify when a new report is published.
+    pub notify_reports: bool,
+    /// How often news feeds are polled in the background.
+    pub feed_refresh_interval_mins: u64,
+    /// Fields this build does not know, kept so a newer or older build's settings survive a save.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Self {
+            version: SETTINGS_VERSION,
             shortcuts: Vec::new(),
             feeds: Vec::new(),
             weather_enabled: false,
@@ -121,6 +163,25 @@ impl Default for Settings {
                 WidgetLayout { id: "focus_timer".to_string(), x: 1, y: 5, width: 1, height: 2, enabled: true },
             ],
             dev_sensitive_info_hider: false,
+            request_max_retries: 3,
+            request_retry_base_delay_ms: 500,
+            heartbeat_interval_secs: 240,
+            sync_enabled: true,
+            sync_timetable_interval_mins: 60,
+            sync_assessments_interval_mins: 30,
+            sync_notices_interval_mins: 30,
+            sync_messages_interval_mins: 15,
+            notify_new_assessments: true,
+            notify_marks_released: true,
+            notify_notices: true,
+            notify_messages: true,
+            reminder_lead_times_mins: vec![7 * 24 * 60, 24 * 60, 2 * 60],
+            calendar_feed_enabled: false,
+            calendar_feed_port: 47831,
+            sync_reports_interval_mins: 12 * 60,
+            notify_reports: true,
+            feed_refresh_interval_mins: 30,
+            extra: Map::new(),
         }
     }
 }
@@ -134,8 +195,13 @@ pub struct Shortcut {
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Feed {
-    
     pub url: String,
+    /// Display name; the feed's own title is shown when unset.
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Folder the feed is filed under, with `/` between nested folders.
+    #[serde(default)]
+    pub category: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -204,142 +270,139 @@ struct APIError {
 }
 
 impl Settings {
-    /// Load from disk with smart merging; returns default if none.
+    /// Load from disk; returns default if none.
+    /// `gemini_api_key` is decrypted here, and a plaintext key from an older version is
+    /// re-saved encrypted. A key that cannot be decrypted is left out, but stays on disk.
     pub fn load() -> Self {
-        let path = settings_file();
-        if let Ok(mut file) = fs::File::open(&path) {
-            let mut contents = String::new();
-            if file.read_to_string(&mut contents).is_ok() {
-                // Try to parse as the current Settings struct first
-                if let Ok(settings) = serde_json::from_str::<Settings>(&contents) {
-                    return settings;
-                }
-                
-                // If that fails, try to merge with existing JSON
-                if let Ok(existing_json) = serde_json::from_str::<serde_json::Value>(&contents) {
-                    return Self::merge_with_existing(existing_json);
+        let mut settings = Self::load_from_disk();
+        if let Some(key) = settings.gemini_api_key.clone() {
+            if crypto::is_encrypted(&key) {
+                settings.gemini_api_key = match crypto::decrypt_string(&key) {
+                    Ok(key) => Some(key),
+                    Err(e) => {
+                        eprintln!("[Desqta] Failed to decrypt Gemini API key: {}", e);
+                        None
+                    }
+                };
+            } else if !key.is_empty() {
+                if let Err(e) = settings.save() {
+                    eprintln!("[Desqta] Failed to encrypt Gemini API key: {}", e);
                 }
             }
         }
-        Settings::default()
-    }
-
-    /// Smart merge function that preserves existing settings when new fields are added
-    fn merge_with_existing(existing_json: serde_json::Value) -> Self {
-        let mut default_settings = Settings::default();
-        
-        // Helper function to safely extract values with fallbacks
-        let get_string = |json: &serde_json::Value, key: &str, default: &str| {
-            json.get(key)
-                .and_then(|v| v.as_str())
-                .unwrap_or(default)
-                .to_string()
-        };
-        
-        let get_bool = |json: &serde_json::Value, key: &str, default: bool| {
-            json.get(key)
-                .and_then(|v| v.as_bool())
-                .unwrap_or(default)
+        settings
+    }
+
+    /// Load from disk; returns default if none.
+    fn load_from_disk() -> Self {
+        Self::load_file(&settings_file())
+    }
+
+    /// Load `path`, migrating it to `SETTINGS_VERSION` if it is older. The original file
+    /// is copied to `settings.v<N>.bak.json` the first time, then the migrated settings
+    /// replace it so the migration only runs once.
+    fn load_file(path: &Path) -> Self {
+        let Ok(contents) = fs::read_to_string(path) else {
+            return Settings::default();
         };
-        
-        let get_array = |json: &serde_json::Value, key: &str| {
-            json.get(key)
-                .and_then(|v| v.as_array())
-                .cloned()
-                .unwrap_or_default()
+        let Some((settings, stored_version)) = Self::parse_stored(&contents) else {
+            eprintln!("[Desqta] {} is not a settings object; using defaults", path.display());
+            return Settings::default();
         };
+        if stored_version < SETTINGS_VERSION {
+            let backup = path.with_file_name(format!("settings.v{}.bak.json", stored_version));
+            if !backup.exists() {
+                if let Err(e) = fs::write(&backup, &contents) {
+                    eprintln!("[Desqta] Failed to back up v{} settings: {}", stored_version, e);
+                }
+            }
+            match settings.write_to(path) {
+                Ok(()) => println!(
+                    "[Desqta] Migrated settings from v{} to v{} (backup at {})",
+                    stored_version,
+                    SETTINGS_VERSION,
+                    backup.display()
+                ),
+                Err(e) => eprintln!("[Desqta] Failed to save migrated settings: {}", e),
+            }
+        }
+        settings
+    }
 
-        let get_opt_string = |json: &serde_json::Value, key: &str| {
-            json.get(key).and_then(|v| v.as_str()).map(|s| s.to_string())
+    /// Parse stored settings JSON of any version. Returns the settings and the version
+    /// they were stored as, or `None` if `contents` is not a JSON object.
+    fn parse_stored(contents: &str) -> Option<(Self, u32)> {
+        let Ok(Value::Object(mut json)) = serde_json::from_str::<Value>(contents) else {
+            return None;
         };
+        let stored_version = migrate(&mut json);
+        Some((Self::from_fields(json), stored_version))
+    }
 
-        let get_opt_bool = |json: &serde_json::Value, key: &str| {
-            json.get(key).and_then(|v| v.as_bool())
+    /// Build settings a field at a time, so a value of the wrong type falls back to its
+    /// default instead of discarding the whole file. Lists keep their valid entries.
+    fn from_fields(json: Map<String, Value>) -> Self {
+        if let Ok(settings) = serde_json::from_value(Value::Object(json.clone())) {
+            return settings;
+        }
+        let Ok(Value::Object(mut merged)) = serde_json::to_value(Settings::default()) else {
+            return Settings::default();
         };
-
-        // Merge shortcuts
-        let shortcuts_json = get_array(&existing_json, "shortcuts");
-        let mut shortcuts = Vec::new();
-        for shortcut_json in shortcuts_json {
-            if let (Some(name), Some(icon), Some(url)) = (
-                shortcut_json.get("name").and_then(|v| v.as_str()),
-                shortcut_json.get("icon").and_then(|v| v.as_str()),
-                shortcut_json.get("url").and_then(|v| v.as_str()),
-            ) {
-                shortcuts.push(Shortcut {
-                    name: name.to_string(),
-                    icon: icon.to_string(),
-                    url: url.to_string(),
-                });
+        let valid = |merged: &Map<String, Value>| serde_json::from_value::<Settings>(Value::Object(merged.clone())).is_ok();
+        for (key, value) in json {
+            let default = merged.insert(key.clone(), value.clone());
+            if valid(&merged) {
+                continue;
             }
+            let kept: Vec<Value> = match value {
+                Value::Array(items) => items
+                    .into_iter()
+                    .filter(|item| {
+                        merged.insert(key.clone(), Value::Array(vec![item.clone()]));
+                        valid(&merged)
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            eprintln!("[Desqta] Ignoring invalid value for setting {}", key);
+            match default {
+                _ if !kept.is_empty() => merged.insert(key, Value::Array(kept)),
+                Some(default) => merged.insert(key, default),
+                None => merged.remove(&key),
+            };
         }
-        default_settings.shortcuts = shortcuts;
-
-        // Merge feeds
-        let feeds_json = get_array(&existing_json, "feeds");
-        let mut feeds = Vec::new();
-        for feed_json in feeds_json {
-            if let Some(url) = feed_json.get("url").and_then(|v| v.as_str()) {
-                feeds.push(Feed {
-                    url: url.to_string(),
-                });
+        serde_json::from_value(Value::Object(merged)).unwrap_or_default()
+    }
+
+    /// Persist to disk, with `gemini_api_key` encrypted.
+    pub fn save(&self) -> io::Result<()> {
+        self.write_to(&settings_file())
+    }
+
+    fn write_to(&self, path: &Path) -> io::Result<()> {
+        let mut stored = self.clone();
+        match self.gemini_api_key.as_deref() {
+            Some(key) if crypto::is_encrypted(key) => {}
+            Some(key) if !key.is_empty() => {
+                stored.gemini_api_key = Some(crypto::encrypt_string(key).map_err(io::Error::other)?);
             }
-        }
-        default_settings.feeds = feeds;
-
-        // Merge individual settings with fallbacks to defaults
-        default_settings.weather_enabled = get_bool(&existing_json, "weather_enabled", default_settings.weather_enabled);
-        default_settings.weather_city = get_string(&existing_json, "weather_city", &default_settings.weather_city);
-        default_settings.weather_country = get_string(&existing_json, "weather_country", &default_settings.weather_country);
-        default_settings.reminders_enabled = get_bool(&existing_json, "reminders_enabled", default_settings.reminders_enabled);
-        default_settings.force_use_location = get_bool(&existing_json, "force_use_location", default_settings.force_use_location);
-        default_settings.accent_color = get_string(&existing_json, "accent_color", &default_settings.accent_color);
-        default_settings.theme = get_string(&existing_json, "theme", &default_settings.theme);
-        default_settings.disable_school_picture = get_bool(&existing_json, "disable_school_picture", default_settings.disable_school_picture);
-        default_settings.enhanced_animations = get_bool(&existing_json, "enhanced_animations", default_settings.enhanced_animations);
-        default_settings.gemini_api_key = get_opt_string(&existing_json, "gemini_api_key");
-        default_settings.ai_integrations_enabled = get_opt_bool(&existing_json, "ai_integrations_enabled");
-        default_settings.grade_analyser_enabled = get_opt_bool(&existing_json, "grade_analyser_enabled");
-        default_settings.lesson_summary_analyser_enabled = get_opt_bool(&existing_json, "lesson_summary_analyser_enabled");
-        default_settings.auto_collapse_sidebar = get_bool(&existing_json, "auto_collapse_sidebar", default_settings.auto_collapse_sidebar);
-        default_settings.auto_expand_sidebar_hover = get_bool(&existing_json, "auto_expand_sidebar_hover", default_settings.auto_expand_sidebar_hover);
-        default_settings.global_search_enabled = get_bool(&existing_json, "global_search_enabled", default_settings.global_search_enabled);
-        default_settings.current_theme = get_opt_string(&existing_json, "current_theme");
-        default_settings.dev_sensitive_info_hider = get_bool(&existing_json, "dev_sensitive_info_hider", default_settings.dev_sensitive_info_hider);
-        
-        // Merge widget layout
-        let widget_layout_json = get_array(&existing_json, "widget_layout");
-        let mut widget_layout = Vec::new();
-        for layout_json in widget_layout_json {
-            if let (Some(id), Some(x), Some(y), Some(width), Some(height), Some(enabled)) = (
-                layout_json.get("id").and_then(|v| v.as_str()),
-                layout_json.get("x").and_then(|v| v.as_i64()),
-                layout_json.get("y").and_then(|v| v.as_i64()),
-                layout_json.get("width").and_then(|v| v.as_i64()),
-                layout_json.get("height").and_then(|v| v.as_i64()),
-                layout_json.get("enabled").and_then(|v| v.as_bool()),
-            ) {
-                widget_layout.push(WidgetLayout {
-                    id: id.to_string(),
-                    x: x as i32,
-                    y: y as i32,
-                    width: width as i32,
-                    height: height as i32,
-                    enabled,
-                });
+            // `load` leaves out a key it could not decrypt (e.g. the keyring is still
+            // locked); keep it rather than erase it
+            _ => {
+                if let Some(key) = Self::undecryptable_key(path) {
+                    stored.gemini_api_key = Some(key);
+                }
             }
         }
-        if !widget_layout.is_empty() {
-            default_settings.widget_layout = widget_layout;
-        }
-
-        default_settings
+        crypto::write_atomic(path, serde_json::to_string(&stored).unwrap().as_bytes())
     }
 
-    /// Persist to disk.
-    pub fn save(&self) -> io::Result<()> {
-        let path = settings_file();
-        fs::write(path, serde_json::to_string(self).unwrap())
+    /// The encrypted `gemini_api_key` saved in `path`, if it cannot be decrypted.
+    fn undecryptable_key(path: &Path) -> Option<String> {
+        let contents = fs::read_to_string(path).ok()?;
+        let json: Value = serde_json::from_str(&contents).ok()?;
+        let key = json.get("gemini_api_key")?.as_str()?;
+        (crypto::is_encrypted(key) && crypto::decrypt_string(key).is_err()).then(|| key.to_string())
     }
 
     /// Convert to JSON string for cloud sync
@@ -347,9 +410,50 @@ impl Settings {
         serde_json::to_string(self).map_err(|e| e.to_string())
     }
 
-    /// Create from JSON string for cloud sync
+    /// Create from JSON string for cloud sync, migrating settings uploaded by an older version
     pub fn from_json(json: &str) -> Result<Self, String> {
-        serde_json::from_str(json).map_err(|e| e.to_string())
+        Self::parse_stored(json)
+            .map(|(settings, _)| settings)
+            .ok_or_else(|| "Settings must be a JSON object".to_string())
+    }
+}
+
+/// One step of the settings migration chain, applied to the raw JSON object.
+type Migration = fn(&mut Map<String, Value>);
+
+/// `MIGRATIONS[n]` upgrades version `n + 1` settings to version `n + 2`.
+const MIGRATIONS: &[Migration] = &[v1_to_v2];
+
+/// Upgrade `json` to `SETTINGS_VERSION` in place and return the version it was stored as.
+/// Files without a version predate versioning and are v1. Files from a newer build are
+/// left as they are.
+fn migrate(json: &mut Map<String, Value>) -> u32 {
+    let stored_version = json
+        .get("version")
+        .and_then(Value::as_u64)
+        .map_or(1, |v| v.clamp(1, u32::MAX as u64) as u32);
+    for (step, migration) in MIGRATIONS.iter().enumerate().skip(stored_version as usize - 1) {
+        migration(json);
+        json.insert("version".to_string(), json!(step + 2));
+    }
+    stored_version
+}
+
+/// v1 is every file written before settings were versioned. Its fields all still exist,
+/// but it was loaded leniently: optional fields that were missing came back unset rather
+/// than defaulted, and an empty widget layout was replaced with the default one.
+fn v1_to_v2(json: &mut Map<String, Value>) {
+    for key in [
+        "gemini_api_key",
+        "ai_integrations_enabled",
+        "grade_analyser_enabled",
+        "lesson_summary_analyser_enabled",
+        "current_theme",
+    ] {
+        json.entry(key).or_insert(Value::Null);
+    }
+    if json.get("widget_layout").and_then(Value::as_array).is_some_and(Vec::is_empty) {
+        json.remove("widget_layout");
     }
 }
 
@@ -360,7 +464,9 @@ pub fn get_settings() -> Settings {
 
 #[tauri::command]
 pub fn save_settings(new_settings: Settings) -> Result<(), String> {
-    new_settings.save().map_err(|e| e.to_string())
+    new_settings.save().map_err(|e| e.to_string())?;
+    calendar_feed::apply(&new_settings);
+    Ok(())
 }
 
 #[tauri::command]
@@ -372,35 +478,46 @@ pub fn get_settings_json() -> Result<String, String> {
 #[tauri::command]
 pub fn save_settings_from_json(json: String) -> Result<(), String> {
     let settings = Settings::from_json(&json)?;
-    settings.save().map_err(|e| e.to_string())
+    settings.save().map_err(|e| e.to_string())?;
+    calendar_feed::apply(&settings);
+    Ok(())
+}
+
+/// Turn a failed cloud API response into a `NetError`, preferring the API's own error message.
+async fn cloud_error(response: reqwest::Response) -> NetError {
+    let status = response.status().as_u16();
+    let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+    if let Ok(api_error) = serde_json::from_str::<APIError>(&error_text) {
+        return NetError::http(api_error.statusCode as u16, api_error.statusMessage);
+    }
+    NetError::http(status, error_text)
+}
+
+fn require_cloud_token() -> Result<String, NetError> {
+    CloudToken::load().token.ok_or_else(|| NetError::NotAuthenticated {
+        message: "No cloud token found. Please authenticate first.".to_string(),
+    })
 }
 
 #[tauri::command]
-pub async fn save_cloud_token(token: String) -> Result<CloudUser, String> {
+pub async fn save_cloud_token(token: String) -> Result<CloudUser, NetError> {
     let base_url = "https://accounts.betterseqta.org/api";
     let client = reqwest::Client::new();
     let response = client
-        .get(&format!("{}/auth/me", base_url))
+        .get(format!("{}/auth/me", base_url))
         .header("Authorization", format!("Bearer {}", token))
         .send()
-        .await
-        .map_err(|e| format!("Network error: {}", e))?;
-    let status = response.status();
-    if !status.is_success() {
-        let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-        if let Ok(api_error) = serde_json::from_str::<APIError>(&error_text) {
-            return Err(format!("API Error {}: {}", api_error.statusCode, api_error.statusMessage));
-        }
-        return Err(format!("Authentication failed: {} - {}", status, error_text));
+        .await?;
+    if !response.status().is_success() {
+        return Err(cloud_error(response).await);
     }
-    let user_text = response.text().await
-        .map_err(|e| format!("Failed to read response: {}", e))?;
+    let user_text = response.text().await?;
     let user: CloudUser = serde_json::from_str(&user_text)
-        .map_err(|e| format!("Failed to parse user response: {} - Raw response: {}", e, user_text))?;
+        .map_err(|e| NetError::decode(format!("Failed to parse user response: {} - Raw response: {}", e, user_text)))?;
     let mut cloud_token = CloudToken::load();
     cloud_token.token = Some(token);
     cloud_token.user = Some(user.clone());
-    cloud_token.save().map_err(|e| e.to_string())?;
+    cloud_token.save()?;
     Ok(user)
 }
 
@@ -411,65 +528,53 @@ pub fn get_cloud_user() -> Option<CloudUser> {
 }
 
 #[tauri::command]
-pub fn clear_cloud_token() -> Result<(), String> {
-    CloudToken::clear_file().map_err(|e| e.to_string())
+pub fn clear_cloud_token() -> Result<(), NetError> {
+    CloudToken::clear_file().map_err(NetError::from)
 }
 
 #[tauri::command]
-pub async fn upload_settings_to_cloud() -> Result<(), String> {
-    let cloud_token = CloudToken::load();
-    let token = cloud_token.token.clone().ok_or("No cloud token found. Please authenticate first.")?;
+pub async fn upload_settings_to_cloud() -> Result<(), NetError> {
+    let token = require_cloud_token()?;
     let base_url = "https://accounts.betterseqta.org/api";
     let settings = Settings::load();
-    let settings_json = settings.to_json()?;
+    let settings_json = settings.to_json().map_err(NetError::decode)?;
     let client = reqwest::Client::new();
     let form = reqwest::multipart::Form::new()
         .part("file", reqwest::multipart::Part::text(settings_json)
             .file_name("desqta-settings.json")
             .mime_str("application/json").unwrap());
     let response = client
-        .post(&format!("{}/files/upload", base_url))
+        .post(format!("{}/files/upload", base_url))
         .header("Authorization", format!("Bearer {}", token))
         .multipart(form)
         .send()
-        .await
-        .map_err(|e| format!("Network error: {}", e))?;
-    let status = response.status();
-    if !status.is_success() {
-        let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(format!("Upload failed: {} - {}", status, error_text));
+        .await?;
+    if !response.status().is_success() {
+        return Err(cloud_error(response).await);
     }
     Ok(())
 }
 
 #[tauri::command]
-pub async fn download_settings_from_cloud() -> Result<Settings, String> {
-    let cloud_token = CloudToken::load();
-    let token = cloud_token.token.clone().ok_or("No cloud token found. Please authenticate first.")?;
+pub async fn download_settings_from_cloud() -> Result<Settings, NetError> {
+    let token = require_cloud_token()?;
     let base_url = "https://accounts.betterseqta.org/api";
     let client = reqwest::Client::new();
     let response = client
-        .get(&format!("{}/files/list", base_url))
+        .get(format!("{}/files/list", base_url))
         .header("Authorization", format!("Bearer {}", token))
         .query(&[("search", "desqta-settings.json"), ("limit", "10")])
         .send()
-        .await
-        .map_err(|e| format!("Network error: {}", e))?;
-    let status = response.status();
-    if !status.is_success() {
-        let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-        if let Ok(api_error) = serde_json::from_str::<APIError>(&error_text) {
-            return Err(format!("API Error {}: {}", api_error.statusCode, api_error.statusMessage));
-        }
-        return Err(format!("List files failed: {} - {}", status, error_text));
+        .await?;
+    if !response.status().is_success() {
+        return Err(cloud_error(response).await);
     }
-    let response_text = response.text().await
-        .map_err(|e| format!("Failed to read response: {}", e))?;
+    let response_text = response.text().await?;
     let file_list: FileListResponse = serde_json::from_str(&response_text)
-        .map_err(|e| format!("Failed to parse response: {} - Raw response: {}", e, response_text))?;
+        .map_err(|e| NetError::decode(format!("Failed to parse response: {} - Raw response: {}", e, response_text)))?;
     let settings_file = file_list.files.iter()
         .find(|file| file.filename == "desqta-settings.json")
-        .ok_or("No settings file found in cloud")?;
+        .ok_or_else(|| NetError::NotFound { message: "No settings file found in cloud".to_string() })?;
     let download_url = if settings_file.is_public {
         format!("{}/files/public/{}", base_url, settings_file.stored_name)
     } else {
@@ -482,48 +587,356 @@ pub async fn download_settings_from_cloud() -> Result<Settings, String> {
     }
     let response = request_builder
         .send()
-        .await
-        .map_err(|e| format!("Network error: {}", e))?;
-    let status = response.status();
-    if !status.is_success() {
-        let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-        if let Ok(api_error) = serde_json::from_str::<APIError>(&error_text) {
-            return Err(format!("API Error {}: {} - StoredName: {}, IsPublic: {}", 
-                              api_error.statusCode, api_error.statusMessage, 
-                              settings_file.stored_name, settings_file.is_public));
-        }
-        return Err(format!("Download failed: {} - {} - StoredName: {}, IsPublic: {}", 
-                          status, error_text, settings_file.stored_name, settings_file.is_public));
+        .await?;
+    if !response.status().is_success() {
+        eprintln!(
+            "[Desqta] Settings download failed - StoredName: {}, IsPublic: {}",
+            settings_file.stored_name, settings_file.is_public
+        );
+        return Err(cloud_error(response).await);
     }
-    let settings_text = response.text().await
-        .map_err(|e| format!("Failed to read response: {}", e))?;
-    Settings::from_json(&settings_text)
+    let settings_text = response.text().await?;
+    Settings::from_json(&settings_text).map_err(NetError::decode)
 }
 
 #[tauri::command]
-pub async fn check_cloud_settings() -> Result<bool, String> {
-    let cloud_token = CloudToken::load();
-    let token = cloud_token.token.clone().ok_or("No cloud token found. Please authenticate first.")?;
+pub async fn check_cloud_settings() -> Result<bool, NetError> {
+    let token = require_cloud_token()?;
     let base_url = "https://accounts.betterseqta.org/api";
     let client = reqwest::Client::new();
     let response = client
-        .get(&format!("{}/files/list", base_url))
+        .get(format!("{}/files/list", base_url))
         .header("Authorization", format!("Bearer {}", token))
         .query(&[("search", "desqta-settings.json"), ("limit", "1")])
         .send()
-        .await
-        .map_err(|e| format!("Network error: {}", e))?;
-    let status = response.status();
-    if !status.is_success() {
-        let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-        if let Ok(api_error) = serde_json::from_str::<APIError>(&error_text) {
-            return Err(format!("API Error {}: {}", api_error.statusCode, api_error.statusMessage));
-        }
-        return Err(format!("Check failed: {} - {}", status, error_text));
+        .await?;
+    if !response.status().is_success() {
+        return Err(cloud_error(response).await);
     }
-    let response_text = response.text().await
-        .map_err(|_| "Failed to read response")?;
+    let response_text = response.text().await?;
     let file_list: FileListResponse = serde_json::from_str(&response_text)
-        .map_err(|e| format!("Failed to parse response: {} - Raw response: {}", e, response_text))?;
+        .map_err(|e| NetError::decode(format!("Failed to parse response: {} - Raw response: {}", e, response_text)))?;
     Ok(!file_list.files.is_empty())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: Value) -> (Settings, u32) {
+        Settings::parse_stored(&value.to_string()).expect("settings object")
+    }
+
+    /// Every field of the settings as they were before versioning, with non-default values.
+    fn v1_baseline() -> Value {
+        json!({
+            "shortcuts": [{ "name": "Mail", "icon": "📧", "url": "https://mail.example.com" }],
+            "feeds": [{ "url": "https://example.com/rss.xml" }],
+            "weather_enabled": true,
+            "weather_city": "Perth",
+            "weather_country": "AU",
+            "reminders_enabled": false,
+            "force_use_location": true,
+            "accent_color": "#ff0000",
+            "theme": "dark",
+            "disable_school_picture": true,
+            "enhanced_animations": false,
+            "gemini_api_key": null,
+            "ai_integrations_enabled": true,
+            "grade_analyser_enabled": false,
+            "lesson_summary_analyser_enabled": null,
+            "auto_collapse_sidebar": true,
+            "auto_expand_sidebar_hover": true,
+            "global_search_enabled": false,
+            "current_theme": "sunset",
+            "widget_layout": [{ "id": "notices", "x": 0, "y": 0, "width": 2, "height": 1, "enabled": false }],
+            "dev_sensitive_info_hider": true
+        })
+    }
+
+    #[test]
+    fn loads_v1_baseline() {
+        let (settings, stored_version) = parse(v1_baseline());
+        assert_eq!(stored_version, 1);
+        assert_eq!(settings.version, SETTINGS_VERSION);
+        assert_eq!(settings.shortcuts[0].name, "Mail");
+        assert_eq!(settings.feeds[0].url, "https://example.com/rss.xml");
+        assert_eq!(settings.feeds[0].title, None);
+        assert!(settings.weather_enabled);
+        assert_eq!(settings.weather_city, "Perth");
+        assert!(!settings.reminders_enabled);
+        assert_eq!(settings.accent_color, "#ff0000");
+        assert_eq!(settings.theme, "dark");
+        assert!(!settings.enhanced_animations);
+        assert_eq!(settings.ai_integrations_enabled, Some(true));
+        assert_eq!(settings.grade_analyser_enabled, Some(false));
+        assert_eq!(settings.lesson_summary_analyser_enabled, None);
+        assert_eq!(settings.current_theme.as_deref(), Some("sunset"));
+        assert_eq!(settings.widget_layout.len(), 1);
+        assert!(!settings.widget_layout[0].enabled);
+        assert!(settings.dev_sensitive_info_hider);
+        // Fields added since get their defaults
+        assert_eq!(settings.request_max_retries, Settings::default().request_max_retries);
+        assert_eq!(settings.feed_refresh_interval_mins, Settings::default().feed_refresh_interval_mins);
+        assert!(settings.extra.is_empty());
+    }
+
+    #[test]
+    fn loads_v1_with_later_fields() {
+        let mut json = v1_baseline();
+        let fields = json!({
+            "request_max_retries": 5,
+            "heartbeat_interval_secs": 0,
+            "sync_enabled": false,
+            "sync_messages_interval_mins": 5,
+            "notify_marks_released": false,
+            "reminder_lead_times_mins": [60],
+            "calendar_feed_enabled": true,
+            "calendar_feed_port": 50000,
+            "sync_reports_interval_mins": 120,
+            "feed_refresh_interval_mins": 10,
+            "feeds": [{ "url": "https://example.com/atom", "title": "Example", "category": "News/Local" }]
+        });
+        json.as_object_mut().unwrap().extend(fields.as_object().unwrap().clone());
+
+        let (settings, stored_version) = parse(json);
+        assert_eq!(stored_version, 1);
+        assert_eq!(settings.request_max_retries, 5);
+        assert_eq!(settings.heartbeat_interval_secs, 0);
+        assert!(!settings.sync_enabled);
+        assert_eq!(settings.sync_messages_interval_mins, 5);
+        assert!(!settings.notify_marks_released);
+        assert_eq!(settings.reminder_lead_times_mins, vec![60]);
+        assert!(settings.calendar_feed_enabled);
+        assert_eq!(settings.calendar_feed_port, 50000);
+        assert_eq!(settings.sync_reports_interval_mins, 120);
+        assert_eq!(settings.feed_refresh_interval_mins, 10);
+        assert_eq!(settings.feeds[0].title.as_deref(), Some("Example"));
+        assert_eq!(settings.feeds[0].category.as_deref(), Some("News/Local"));
+    }
+
+    #[test]
+    fn v1_feeds_without_a_url_are_dropped() {
+        let (settings, _) = parse(json!({
+            "feeds": ["https://a.example/feed", { "url": "https://b.example/feed" }, { "title": "No url" }],
+            "theme": null,
+            "weather_enabled": null,
+            "shortcuts": null,
+            "current_theme": null
+        }));
+        let urls: Vec<_> = settings.feeds.iter().map(|f| f.url.as_str()).collect();
+        assert_eq!(urls, ["https://b.example/feed"]);
+        assert_eq!(settings.theme, "system");
+        assert!(!settings.weather_enabled);
+        assert!(settings.shortcuts.is_empty());
+        assert_eq!(settings.current_theme, None);
+    }
+
+    #[test]
+    fn v1_missing_optional_fields_are_unset() {
+        let (settings, _) = parse(json!({ "theme": "light" }));
+        assert_eq!(settings.gemini_api_key, None);
+        assert_eq!(settings.ai_integrations_enabled, None);
+        assert_eq!(settings.grade_analyser_enabled, None);
+        assert_eq!(settings.lesson_summary_analyser_enabled, None);
+        assert_eq!(settings.current_theme, None);
+
+        // Only v1 files get this treatment; a new file takes the defaults
+        let (settings, _) = parse(json!({ "version": SETTINGS_VERSION }));
+        assert_eq!(settings.ai_integrations_enabled, Some(false));
+        assert_eq!(settings.grade_analyser_enabled, Some(true));
+    }
+
+    #[test]
+    fn v1_empty_widget_layout_gets_the_default() {
+        let (settings, _) = parse(json!({ "widget_layout": [] }));
+        assert_eq!(settings.widget_layout.len(), Settings::default().widget_layout.len());
+
+        let (settings, _) = parse(json!({ "version": SETTINGS_VERSION, "widget_layout": [] }));
+        assert!(settings.widget_layout.is_empty());
+    }
+
+    /// A settings.json written by the last unversioned release, as `save()` wrote it.
+    const BASELINE_FILE: &str = r##"{"shortcuts":[{"name":"Mail","icon":"📧","url":"https://mail.example.com"}],"feeds":[{"url":"https://example.com/rss.xml","title":null,"category":null}],"weather_enabled":false,"weather_city":"","weather_country":"","reminders_enabled":true,"force_use_location":false,"accent_color":"#3b82f6","theme":"system","disable_school_picture":false,"enhanced_animations":true,"gemini_api_key":null,"ai_integrations_enabled":false,"grade_analyser_enabled":true,"lesson_summary_analyser_enabled":true,"auto_collapse_sidebar":false,"auto_expand_sidebar_hover":false,"global_search_enabled":true,"current_theme":"default","widget_layout":[{"id":"shortcuts","x":0,"y":0,"width":2,"height":1,"enabled":true},{"id":"notices","x":0,"y":1,"width":1,"height":2,"enabled":false}],"dev_sensitive_info_hider":false,"request_max_retries":2,"request_retry_base_delay_ms":500,"heartbeat_interval_secs":240,"sync_enabled":true,"sync_timetable_interval_mins":60,"sync_assessments_interval_mins":30,"sync_notices_interval_mins":30,"sync_messages_interval_mins":15,"notify_new_assessments":true,"notify_marks_released":false,"notify_notices":true,"notify_messages":true,"reminder_lead_times_mins":[1440],"calendar_feed_enabled":false,"calendar_feed_port":47831,"sync_reports_interval_mins":720,"notify_reports":true,"feed_refresh_interval_mins":30}"##;
+
+    /// A settings.json from before the AI and layout options existed.
+    const EARLY_FILE: &str = r##"{"shortcuts":[],"feeds":[{"url":"https://example.com/feed"},{"name":"broken"}],"weather_enabled":true,"weather_city":"Hobart","weather_country":"AU","reminders_enabled":true,"accent_color":"#10b981","theme":"dark","widget_layout":[]}"##;
+
+    fn load_fixture(name: &str, contents: &str) -> Settings {
+        let dir = std::env::temp_dir().join(format!("desqta-settings-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.json");
+        fs::write(&path, contents).unwrap();
+        let settings = Settings::load_file(&path);
+        fs::remove_dir_all(&dir).unwrap();
+        settings
+    }
+
+    #[test]
+    fn loads_a_file_saved_by_the_unversioned_release() {
+        let settings = load_fixture("baseline", BASELINE_FILE);
+        assert_eq!(settings.version, SETTINGS_VERSION);
+        assert_eq!(settings.shortcuts[0].url, "https://mail.example.com");
+        assert_eq!(settings.feeds[0].url, "https://example.com/rss.xml");
+        assert_eq!(settings.ai_integrations_enabled, Some(false));
+        assert_eq!(settings.lesson_summary_analyser_enabled, Some(true));
+        assert_eq!(settings.current_theme.as_deref(), Some("default"));
+        assert_eq!(settings.widget_layout.len(), 2);
+        assert_eq!((settings.widget_layout[1].height, settings.widget_layout[1].enabled), (2, false));
+        assert_eq!(settings.request_max_retries, 2);
+        assert!(!settings.notify_marks_released);
+        assert_eq!(settings.reminder_lead_times_mins, vec![1440]);
+        assert!(settings.extra.is_empty());
+    }
+
+    #[test]
+    fn loads_an_early_file() {
+        let settings = load_fixture("early", EARLY_FILE);
+        let defaults = Settings::default();
+        assert_eq!(settings.feeds.len(), 1);
+        assert_eq!(settings.weather_city, "Hobart");
+        assert_eq!(settings.theme, "dark");
+        assert_eq!(settings.accent_color, "#10b981");
+        assert!(!settings.force_use_location);
+        assert!(settings.enhanced_animations);
+        assert_eq!(settings.ai_integrations_enabled, None);
+        assert_eq!(settings.grade_analyser_enabled, None);
+        assert_eq!(settings.lesson_summary_analyser_enabled, None);
+        assert_eq!(settings.widget_layout.len(), defaults.widget_layout.len());
+        assert_eq!(settings.request_max_retries, defaults.request_max_retries);
+        assert_eq!(settings.calendar_feed_port, defaults.calendar_feed_port);
+    }
+
+    #[test]
+    fn current_version_without_fields_gives_defaults() {
+        let (settings, stored_version) = parse(json!({ "version": SETTINGS_VERSION }));
+        assert_eq!(stored_version, SETTINGS_VERSION);
+        assert_eq!(
+            serde_json::to_value(&settings).unwrap(),
+            serde_json::to_value(Settings::default()).unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_values_fall_back_to_defaults() {
+        let (settings, _) = parse(json!({
+            "version": SETTINGS_VERSION,
+            "weather_enabled": "yes",
+            "calendar_feed_port": 70000,
+            "accent_color": "#00ff00",
+            "widget_layout": [
+                { "id": "notices", "x": 0, "y": 0, "width": 2, "height": 1, "enabled": true },
+                { "id": "broken" }
+            ]
+        }));
+        assert!(!settings.weather_enabled);
+        assert_eq!(settings.calendar_feed_port, Settings::default().calendar_feed_port);
+        assert_eq!(settings.accent_color, "#00ff00");
+        assert_eq!(settings.widget_layout.len(), 1);
+        assert_eq!(settings.widget_layout[0].id, "notices");
+    }
+
+    #[test]
+    fn not_an_object_is_rejected() {
+        assert!(Settings::parse_stored("[1, 2]").is_none());
+        assert!(Settings::parse_stored("not json").is_none());
+        assert!(Settings::from_json("null").is_err());
+    }
+
+    #[test]
+    fn unknown_fields_survive_a_round_trip() {
+        let mut json = v1_baseline();
+        json["future_option"] = json!({ "nested": [1, 2, 3] });
+        json["legacy_flag"] = json!(true);
+        let (settings, _) = parse(json);
+        assert_eq!(settings.extra["future_option"], json!({ "nested": [1, 2, 3] }));
+
+        let saved: Value = serde_json::from_str(&settings.to_json().unwrap()).unwrap();
+        assert_eq!(saved["future_option"], json!({ "nested": [1, 2, 3] }));
+        assert_eq!(saved["legacy_flag"], json!(true));
+        assert_eq!(saved["version"], json!(SETTINGS_VERSION));
+        assert!(saved.get("extra").is_none());
+    }
+
+    #[test]
+    fn current_version_round_trips() {
+        let settings = Settings {
+            weather_city: "Adelaide".to_string(),
+            feeds: vec![Feed {
+                url: "https://example.com/feed".to_string(),
+                title: Some("Example".to_string()),
+                category: None,
+            }],
+            ..Settings::default()
+        };
+        let (loaded, stored_version) = Settings::parse_stored(&settings.to_json().unwrap()).unwrap();
+        assert_eq!(stored_version, SETTINGS_VERSION);
+        assert_eq!(serde_json::to_value(&loaded).unwrap(), serde_json::to_value(&settings).unwrap());
+    }
+
+    #[test]
+    fn newer_version_is_left_alone() {
+        let (settings, stored_version) = parse(json!({
+            "version": SETTINGS_VERSION + 1,
+            "theme": "light",
+            "renamed_field": "kept"
+        }));
+        assert_eq!(stored_version, SETTINGS_VERSION + 1);
+        assert_eq!(settings.version, SETTINGS_VERSION + 1);
+        assert_eq!(settings.theme, "light");
+        assert_eq!(settings.extra["renamed_field"], json!("kept"));
+    }
+
+    #[test]
+    fn undecryptable_gemini_key_is_not_erased() {
+        let dir = std::env::temp_dir().join(format!("desqta-settings-key-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.json");
+        let stored = "desqta-enc:v1:not-base64";
+        let mut file = serde_json::to_value(Settings::default()).unwrap();
+        file["gemini_api_key"] = json!(stored);
+        fs::write(&path, file.to_string()).unwrap();
+
+        // As saved by the settings page after the key failed to decrypt
+        let mut settings = Settings::load_file(&path);
+        settings.gemini_api_key = Some(String::new());
+        settings.theme = "light".to_string();
+        settings.write_to(&path).unwrap();
+
+        let saved: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["gemini_api_key"], json!(stored));
+        assert_eq!(saved["theme"], json!("light"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn migrating_a_file_writes_a_backup_once() {
+        let dir = std::env::temp_dir().join(format!("desqta-settings-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.json");
+        let backup = dir.join("settings.v1.bak.json");
+        let original = v1_baseline().to_string();
+        fs::write(&path, &original).unwrap();
+
+        let settings = Settings::load_file(&path);
+        assert_eq!(settings.weather_city, "Perth");
+        assert_eq!(fs::read_to_string(&backup).unwrap(), original);
+        let migrated: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(migrated["version"], json!(SETTINGS_VERSION));
+        assert_eq!(migrated["weather_city"], json!("Perth"));
+
+        // Already current: no further backup
+        fs::remove_file(&backup).unwrap();
+        Settings::load_file(&path);
+        assert!(!backup.exists());
+
+        // An existing backup is kept, and the migrated file is still saved
+        fs::write(&backup, "first").unwrap();
+        fs::write(&path, &original).unwrap();
+        Settings::load_file(&path);
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "first");
+        let migrated: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(migrated["version"], json!(SETTINGS_VERSION));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
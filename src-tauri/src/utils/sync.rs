@@ -0,0 +1,544 @@
+use chrono::{Duration as ChronoDuration, Local};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::Notify;
+use tokio::time::{timeout, Duration, Instant};
+
+use crate::neterror::NetError;
+use crate::notifier;
+use crate::report_archive;
+use crate::seqta::{self, Assessment, MessageSummary, Notice, Report, TimetableLesson};
+use crate::session;
+use crate::settings::Settings;
+
+/// How often the scheduler wakes up to see whether anything is due.
+const TICK: Duration = Duration::from_secs(60);
+/// Longest we wait between attempts while SEQTA is unreachable.
+const MAX_BACKOFF: Duration = Duration::from_secs(30 * 60);
+/// Days of timetable kept fresh, starting today.
+const TIMETABLE_DAYS: i64 = 14;
+/// Inbox messages fetched per sync; matches what the messages page loads.
+const MESSAGE_PAGE_SIZE: u32 = 100;
+
+static WAKE: OnceLock<Notify> = OnceLock::new();
+static FORCE: AtomicBool = AtomicBool::new(false);
+
+fn wake() -> &'static Notify {
+    WAKE.get_or_init(Notify::new)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Dataset {
+    Timetable,
+    Assessments,
+    Notices,
+    Messages,
+    Reports,
+}
+
+impl Dataset {
+    const ALL: [Dataset; 5] = [
+        Dataset::Timetable,
+        Dataset::Assessments,
+        Dataset::Notices,
+        Dataset::Messages,
+        Dataset::Reports,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Dataset::Timetable => "timetable",
+            Dataset::Assessments => "assessments",
+            Dataset::Notices => "notices",
+            Dataset::Messages => "messages",
+            Dataset::Reports => "reports",
+        }
+    }
+
+    fn interval(self, settings: &Settings) -> Duration {
+        let mins = match self {
+            Dataset::Timetable => settings.sync_timetable_interval_mins,
+            Dataset::Assessments => settings.sync_assessments_interval_mins,
+            Dataset::Notices => settings.sync_notices_interval_mins,
+            Dataset::Messages => settings.sync_messages_interval_mins,
+            Dataset::Reports => settings.sync_reports_interval_mins,
+        };
+        Duration::from_secs(mins.max(1) * 60)
+    }
+}
+
+/// An item that appeared, disappeared or changed since the last sync.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangedItem {
+    pub id: String,
+    pub title: String,
+}
+
+/// Payload of the `data-updated` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct DataUpdate {
+    pub dataset: &'static str,
+    pub added: Vec<ChangedItem>,
+    pub removed: Vec<ChangedItem>,
+    pub changed: Vec<ChangedItem>,
+}
+
+impl DataUpdate {
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// What the last sync saw of an item, enough to tell whether it has changed since.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Seen {
+    title: String,
+    /// Hash of the fields whose change is worth reporting.
+    fingerprint: String,
+    /// Which part of the dataset the item came from (a date or subject), so an item that
+    /// was not fetched this time is not mistaken for one that was removed.
+    scope: String,
+}
+
+impl Seen {
+    fn new(title: String, fields: &[&str], scope: impl Into<String>) -> Self {
+        let mut hasher = Sha256::new();
+        for field in fields {
+            hasher.update(field.as_bytes());
+            hasher.update([0]);
+        }
+        let fingerprint = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+        Seen { title, fingerprint, scope: scope.into() }
+    }
+}
+
+/// How an item recorded by the last sync relates to what was fetched this time.
+enum Coverage {
+    /// Its part of the dataset was fetched, so if it is missing it was removed.
+    Fetched,
+    /// Its part was not fetched this time; remember it until it is.
+    Unfetched,
+    /// It has aged out of the dataset; forget it.
+    Expired,
+}
+
+/// The scheduler's own record of every dataset as of its last sync, by dataset and item key.
+/// Kept apart from the offline database, which pages fill in too.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SyncState(HashMap<String, HashMap<String, Seen>>);
+
+/// Location: `$DATA_DIR/DesQTA/profiles/<id>/sync_state.json`
+fn state_file() -> PathBuf {
+    let mut dir = session::profile_dir();
+    dir.push("sync_state.json");
+    dir
+}
+
+impl SyncState {
+    fn load() -> Self {
+        fs::read_to_string(state_file())
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        if let Err(e) = fs::write(state_file(), serde_json::to_string(self).unwrap()) {
+            eprintln!("[Desqta] Failed to save sync state: {}", e);
+        }
+    }
+}
+
+/// Compare what SEQTA returned with what the last sync saw, and record it for the next one.
+/// The first sync of a dataset only records, so it reports nothing.
+fn diff<T>(
+    dataset: Dataset,
+    after: &[T],
+    key: impl Fn(&T) -> String,
+    seen: impl Fn(&T) -> Seen,
+    coverage: impl Fn(&str) -> Coverage,
+) -> DataUpdate {
+    let mut state = SyncState::load();
+    let before = state.0.remove(dataset.name());
+    let after: HashMap<String, Seen> = after.iter().map(|item| (key(item), seen(item))).collect();
+    let (update, recorded) = compare(dataset, before, after, coverage);
+    state.0.insert(dataset.name().to_string(), recorded);
+    state.save();
+    update
+}
+
+/// The changes from `before` (`None` on the first sync) to `after`, and what to record
+/// for the next sync.
+fn compare(
+    dataset: Dataset,
+    before: Option<HashMap<String, Seen>>,
+    after: HashMap<String, Seen>,
+    coverage: impl Fn(&str) -> Coverage,
+) -> (DataUpdate, HashMap<String, Seen>) {
+    let first_sync = before.is_none();
+    let before = before.unwrap_or_default();
+    let item = |k: &String, v: &Seen| ChangedItem { id: k.clone(), title: v.title.clone() };
+
+    let mut update = DataUpdate {
+        dataset: dataset.name(),
+        added: Vec::new(),
+        removed: Vec::new(),
+        changed: Vec::new(),
+    };
+    for (k, new) in &after {
+        match before.get(k) {
+            None => update.added.push(item(k, new)),
+            Some(old) if old.fingerprint != new.fingerprint => update.changed.push(item(k, new)),
+            Some(_) => {}
+        }
+    }
+    let mut recorded = HashMap::new();
+    for (k, old) in before.into_iter().filter(|(k, _)| !after.contains_key(k)) {
+        match coverage(&old.scope) {
+            Coverage::Fetched => update.removed.push(item(&k, &old)),
+            Coverage::Unfetched => {
+                recorded.insert(k, old);
+            }
+            Coverage::Expired => {}
+        }
+    }
+    recorded.extend(after);
+
+    if first_sync {
+        update.added.clear();
+        update.removed.clear();
+        update.changed.clear();
+    }
+    (update, recorded)
+}
+
+fn date_string(days_from_today: i64) -> String {
+    (Local::now().date_naive() + ChronoDuration::days(days_from_today))
+        .format("%Y-%m-%d")
+        .to_string()
+}
+
+async fn sync_timetable() -> Result<DataUpdate, NetError> {
+    let (from, until) = (date_string(0), date_string(TIMETABLE_DAYS - 1));
+    let after = seqta::timetable(&from, &until, true).await?;
+    Ok(diff(
+        Dataset::Timetable,
+        &after,
+        |l: &TimetableLesson| format!("{}|{}|{}", l.date, l.from, l.code),
+        |l| {
+            Seen::new(
+                format!("{} on {} at {}", l.description, l.date, l.from.get(..5).unwrap_or(&l.from)),
+                &[&l.until, l.room.as_deref().unwrap_or(""), l.staff.as_deref().unwrap_or("")],
+                l.date.clone(),
+            )
+        },
+        |date| match date {
+            d if d < from.as_str() => Coverage::Expired,
+            d if d > until.as_str() => Coverage::Unfetched,
+            _ => Coverage::Fetched,
+        },
+    ))
+}
+
+/// Upcoming assessments plus past ones for current subjects, so released marks are picked up.
+async fn sync_assessments(app: &AppHandle) -> Result<DataUpdate, NetError> {
+    let mut after = seqta::upcoming_assessments(true).await?;
+    let folders = seqta::get_subjects().await?;
+    for subject in folders.iter().filter(|f| f.active).flat_map(|f| &f.subjects) {
+        match seqta::past_assessments(subject.programme, subject.metaclass, true).await {
+            Ok(past) => {
+                let past: Vec<Assessment> = past.into_iter().filter(|p| !after.iter().any(|a| a.id == p.id)).collect();
+                after.extend(past);
+            }
+            Err(e) if e.is_unreachable() => return Err(e),
+            Err(e) => eprintln!("[Desqta] Failed to sync past assessments for {}: {}", subject.code, e),
+        }
+    }
+
+    notifier::assessments_fetched(app, &after);
+
+    // Only subjects we fetched can have lost assessments; older subjects are kept as they were
+    let fetched: Vec<String> = after.iter().map(|a| a.metaclass_id.to_string()).collect();
+    Ok(diff(
+        Dataset::Assessments,
+        &after,
+        |a: &Assessment| a.id.to_string(),
+        |a| {
+            Seen::new(
+                format!("{} ({})", a.title, a.subject),
+                &[&a.due, a.status.as_deref().unwrap_or("")],
+                a.metaclass_id.to_string(),
+            )
+        },
+        |metaclass| {
+            if fetched.iter().any(|m| m == metaclass) {
+                Coverage::Fetched
+            } else {
+                Coverage::Unfetched
+            }
+        },
+    ))
+}
+
+async fn sync_notices(app: &AppHandle) -> Result<DataUpdate, NetError> {
+    let after = seqta::notices(&date_string(0), true).await?;
+    notifier::notices_fetched(app, &after);
+    // Today's notices are the whole dataset: one that is no longer shown was removed
+    Ok(diff(
+        Dataset::Notices,
+        &notice_keys(&after),
+        |(key, _): &(String, &Notice)| key.clone(),
+        |(_, n)| Seen::new(n.title.clone(), &[&n.contents], ""),
+        |_| Coverage::Fetched,
+    ))
+}
+
+/// Each notice keyed by its id, or where SEQTA sends none by its title, label and author
+/// plus a count so identical-looking notices stay apart.
+fn notice_keys(notices: &[Notice]) -> Vec<(String, &Notice)> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    notices
+        .iter()
+        .map(|n| {
+            let key = match n.id {
+                Some(id) => id.to_string(),
+                None => {
+                    let key = format!("{}|{}|{}", n.title, n.label_title, n.staff);
+                    let count = counts.entry(key.clone()).or_default();
+                    *count += 1;
+                    if *count == 1 { key } else { format!("{}|{}", key, count) }
+                }
+            };
+            (key, n)
+        })
+        .collect()
+}
+
+async fn sync_messages(app: &AppHandle) -> Result<DataUpdate, NetError> {
+    let after = seqta::messages("inbox", 0, MESSAGE_PAGE_SIZE, true).await?;
+    notifier::messages_fetched(app, &after);
+    // Messages older than the fetched page are not gone, just not fetched
+    let oldest = after.iter().map(|m| m.date.as_str()).min().unwrap_or("").to_string();
+    Ok(diff(
+        Dataset::Messages,
+        &after,
+        |m: &MessageSummary| m.id.to_string(),
+        |m| Seen::new(format!("{}: {}", m.sender, m.subject), &[&m.read.to_string()], m.date.clone()),
+        |date| {
+            if date >= oldest.as_str() {
+                Coverage::Fetched
+            } else {
+                Coverage::Unfetched
+            }
+        },
+    ))
+}
+
+/// Reports missing from the archive are downloaded straight away so they are available
+/// offline. The archive itself decides this, since a report can be listed before it is
+/// archived.
+async fn sync_reports(app: &AppHandle) -> Result<DataUpdate, NetError> {
+    let after = seqta::reports(true).await?;
+    let update = diff(
+        Dataset::Reports,
+        &after,
+        |r: &Report| r.uuid.clone(),
+        |r| Seen::new(format!("{} {} {}", r.year, r.terms, r.types), &[], ""),
+        |_| Coverage::Fetched,
+    );
+
+    for report in after.iter().filter(|r| !report_archive::is_archived(r)) {
+        if let Err(e) = report_archive::archive_report(report).await {
+            if e.is_unreachable() {
+                return Err(e);
+            }
+            eprintln!("[Desqta] Failed to archive report {}: {}", report.uuid, e);
+        }
+    }
+    notifier::reports_fetched(app, &after);
+    Ok(update)
+}
+
+async fn sync_dataset(app: &AppHandle, dataset: Dataset) -> Result<DataUpdate, NetError> {
+    match dataset {
+        Dataset::Timetable => sync_timetable().await,
+        Dataset::Assessments => sync_assessments(app).await,
+        Dataset::Notices => sync_notices(app).await,
+        Dataset::Messages => sync_messages(app).await,
+        Dataset::Reports => sync_reports(app).await,
+    }
+}
+
+/// Spawn the scheduler that keeps the offline database fresh while the app is running,
+/// including while the window is hidden to the tray.
+pub fn start(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut next_due: HashMap<Dataset, Instant> = HashMap::new();
+        let mut consecutive_failures: u32 = 0;
+        let mut backoff_until: Option<Instant> = None;
+        let mut profile: Option<String> = None;
+
+        loop {
+            // Wake on the tick, or early when `sync_now` is called
+            let _ = timeout(TICK, wake().notified()).await;
+
+            let forced = FORCE.swap(false, Ordering::Relaxed);
+            let settings = Settings::load();
+            if !(settings.sync_enabled || forced) || !session::Session::exists() {
+                continue;
+            }
+            // The schedule belongs to the profile it was made for; another one starts afresh
+            let active = session::Session::load_active().0;
+            if active != profile {
+                next_due.clear();
+                consecutive_failures = 0;
+                backoff_until = None;
+                profile = active;
+            }
+            let now = Instant::now();
+            if !forced && backoff_until.is_some_and(|until| now < until) {
+                continue;
+            }
+
+            for dataset in Dataset::ALL {
+                if !forced && next_due.get(&dataset).is_some_and(|due| now < *due) {
+                    continue;
+                }
+
+                match sync_dataset(&app, dataset).await {
+                    Ok(update) => {
+                        consecutive_failures = 0;
+                        backoff_until = None;
+                        next_due.insert(dataset, Instant::now() + dataset.interval(&settings));
+                        if !update.is_empty() {
+                            println!(
+                                "[Desqta] Sync {}: {} added, {} removed, {} changed",
+                                update.dataset,
+                                update.added.len(),
+                                update.removed.len(),
+                                update.changed.len()
+                            );
+                            if let Err(e) = app.emit("data-updated", &update) {
+                                eprintln!("[Desqta] Failed to emit data-updated: {}", e);
+                            }
+                        }
+                    }
+                    Err(e) if e.is_unreachable() => {
+                        consecutive_failures += 1;
+                        let delay = TICK.saturating_mul(2u32.saturating_pow(consecutive_failures - 1)).min(MAX_BACKOFF);
+                        println!("[Desqta] Sync paused for {}s, SEQTA unreachable: {}", delay.as_secs(), e);
+                        backoff_until = Some(Instant::now() + delay);
+                        break;
+                    }
+                    // netgrab has already emitted `session-expired`; wait for the next login
+                    Err(NetError::SessionExpired) => break,
+                    Err(e) => {
+                        eprintln!("[Desqta] Failed to sync {}: {}", dataset.name(), e);
+                        next_due.insert(dataset, Instant::now() + dataset.interval(&settings));
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Refresh every dataset now, ignoring the schedule and any offline backoff.
+#[tauri::command]
+pub fn sync_now() {
+    FORCE.store(true, Ordering::Relaxed);
+    wake().notify_one();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seen(title: &str, field: &str, scope: &str) -> Seen {
+        Seen::new(title.to_string(), &[field], scope)
+    }
+
+    fn items(pairs: &[(&str, Seen)]) -> HashMap<String, Seen> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
+    }
+
+    fn ids(items: &[ChangedItem]) -> Vec<&str> {
+        let mut ids: Vec<&str> = items.iter().map(|i| i.id.as_str()).collect();
+        ids.sort();
+        ids
+    }
+
+    fn notice(id: Option<i64>, title: &str, staff: &str) -> Notice {
+        Notice {
+            id,
+            title: title.to_string(),
+            label_title: "Sport".to_string(),
+            staff: staff.to_string(),
+            colour: None,
+            label: None,
+            contents: String::new(),
+        }
+    }
+
+    #[test]
+    fn notices_with_the_same_title_and_author_stay_apart() {
+        let notices = [
+            notice(None, "Training", "Ms Smith"),
+            notice(None, "Training", "Ms Smith"),
+            notice(None, "Training", "Mr Jones"),
+            notice(Some(42), "Training", "Ms Smith"),
+        ];
+        let keys: Vec<String> = notice_keys(&notices).into_iter().map(|(k, _)| k).collect();
+        assert_eq!(
+            keys,
+            ["Training|Sport|Ms Smith", "Training|Sport|Ms Smith|2", "Training|Sport|Mr Jones", "42"]
+        );
+    }
+
+    #[test]
+    fn first_sync_only_records() {
+        let after = items(&[("1", seen("Essay", "2025-03-01", ""))]);
+        let (update, recorded) = compare(Dataset::Assessments, None, after, |_| Coverage::Fetched);
+        assert!(update.is_empty());
+        assert!(recorded.contains_key("1"));
+    }
+
+    #[test]
+    fn reports_added_removed_and_changed() {
+        let before = items(&[
+            ("1", seen("Essay", "2025-03-01", "")),
+            ("2", seen("Test", "2025-03-02", "")),
+            ("3", seen("Quiz", "2025-03-03", "")),
+        ]);
+        let after = items(&[
+            ("1", seen("Essay", "2025-03-01", "")),
+            ("2", seen("Test", "2025-03-09", "")),
+            ("4", seen("Poster", "2025-03-04", "")),
+        ]);
+        let (update, recorded) = compare(Dataset::Assessments, Some(before), after, |_| Coverage::Fetched);
+        assert_eq!(ids(&update.added), ["4"]);
+        assert_eq!(ids(&update.removed), ["3"]);
+        assert_eq!(ids(&update.changed), ["2"]);
+        assert!(!recorded.contains_key("3"));
+    }
+
+    #[test]
+    fn unfetched_items_are_kept_and_expired_ones_forgotten() {
+        let before = items(&[
+            ("old", seen("Maths", "9:00", "2025-03-01")),
+            ("later", seen("English", "9:00", "2025-04-01")),
+        ]);
+        let (update, recorded) = compare(Dataset::Timetable, Some(before), HashMap::new(), |date| match date {
+            "2025-03-01" => Coverage::Expired,
+            _ => Coverage::Unfetched,
+        });
+        assert!(update.is_empty());
+        assert!(recorded.contains_key("later"));
+        assert!(!recorded.contains_key("old"));
+    }
+}
@@ -33,6 +33,15 @@ export interface UserInfo {
   profilePicture?: string;
 }
 
+export interface ProfileSummary {
+  id: string;
+  display_name: string;
+  base_url: string;
+  last_used: number;
+  active: boolean;
+  logged_in: boolean;
+}
+
 function binaryStringToBase64(binaryStr: string): string {
   let bytes = new Uint8Array(binaryStr.length);
   for (let i = 0; i < binaryStr.length; i++) {
@@ -81,6 +90,16 @@ export const authService = {
       });
       const userInfo: UserInfo = JSON.parse(res).payload;
 
+      // New profiles are named after the school's host until we know who is logged in
+      try {
+        const active = (await invoke<ProfileSummary[]>('list_profiles')).find((p) => p.active);
+        if (active && userInfo.userDesc && active.display_name === new URL(active.base_url).host) {
+          await invoke('rename_profile', { displayName: userInfo.userDesc });
+        }
+      } catch (e) {
+        console.error('Failed to name profile:', e);
+      }
+
       // Check if sensitive content hider mode is enabled
       let devSensitiveInfoHider = false;
       try {
@@ -109,6 +128,28 @@ export const authService = {
     }
   },
 
+  async listProfiles(): Promise<ProfileSummary[]> {
+    return await invoke<ProfileSummary[]>('list_profiles');
+  },
+
+  /** Opens the SEQTA login window; a successful login is saved as a new profile. */
+  async addProfile(seqtaUrl: string): Promise<void> {
+    await this.startLogin(seqtaUrl);
+  },
+
+  async switchProfile(id: string): Promise<void> {
+    cache.delete('userInfo');
+    await invoke('switch_profile', { id });
+  },
+
+  async removeProfile(id: string): Promise<void> {
+    await invoke('remove_profile', { id });
+  },
+
+  async renameProfile(displayName: string, id?: string): Promise<void> {
+    await invoke('rename_profile', { id, displayName });
+  },
+
   async getAPIData(url: string, parameters: Map<string, string>): Promise<any> {
     return await invoke('get_api_data', {
       url,
@@ -1,5 +1,9 @@
 export interface Message {
   id: number;
+  /** Set for items from an RSS folder, which come from the feed aggregator. */
+  feedItemId?: string;
+  /** The article a feed item links to. */
+  link?: string;
   folder: string;
   sender: string;
   to: string;
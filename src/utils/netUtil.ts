@@ -9,6 +9,53 @@ export type SeqtaRequestInit = {
   return_url?: boolean;
 };
 
+// Mirrors `NetError` in src-tauri/src/utils/neterror.rs
+export type NetErrorCode =
+  | 'network'
+  | 'timeout'
+  | 'http'
+  | 'session_expired'
+  | 'not_authenticated'
+  | 'not_found'
+  | 'invalid_url'
+  | 'io'
+  | 'decode'
+  | 'rejected'
+  | 'cancelled';
+
+export interface NetError {
+  code: NetErrorCode;
+  message: string;
+  status?: number;
+  body?: string;
+  attempts?: number;
+}
+
+export class SeqtaError extends Error {
+  code: NetErrorCode;
+  status?: number;
+  attempts?: number;
+
+  constructor(error: NetError) {
+    super(error.message);
+    this.name = 'SeqtaError';
+    this.code = error.code;
+    this.status = error.status;
+    this.attempts = error.attempts;
+  }
+}
+
+export function isNetError(error: unknown): error is NetError {
+  return typeof error === 'object' && error !== null && 'code' in error && 'message' in error;
+}
+
+function toError(error: unknown, fallback: string): Error {
+  if (isNetError(error)) return new SeqtaError(error);
+  if (error instanceof Error) return new Error(error.message);
+  if (typeof error === 'string') return new Error(error);
+  return new Error(fallback);
+}
+
 function getRandomItem<T>(arr: T[]): T {
   return arr[Math.floor(Math.random() * arr.length)];
 }
@@ -309,7 +356,7 @@ export async function seqtaFetch(input: string, init?: SeqtaRequestInit): Promis
     return response;
   } catch (error) {
     console.error('seqtaFetch error:', error);
-    throw new Error(error instanceof Error ? error.message : 'Unknown fetch error');
+    throw toError(error, 'Unknown fetch error');
   }
 }
 
@@ -321,7 +368,7 @@ export async function getRSS(url: string): Promise<any> {
     return response;
   } catch (error) {
     console.error('getRSS error:', error);
-    throw new Error(error instanceof Error ? error.message : 'Unknown fetch error');
+    throw toError(error, 'Unknown fetch error');
   }
 }
 
@@ -336,15 +383,119 @@ export async function openURL(url: string): Promise<any> {
   }
 }
 
-export async function uploadSeqtaFile(fileName: string, filePath: string): Promise<string> {
+export interface UploadedFile {
+  id: number;
+  uuid: string;
+  filename: string;
+  mimetype: string;
+  size: string;
+}
+
+export async function uploadSeqtaFile(
+  fileName: string,
+  filePath: string,
+  uploadId?: string,
+  acceptMimes?: string[],
+): Promise<UploadedFile> {
   try {
-    const response = await invoke<string>('upload_seqta_file', {
-      fileName: fileName,
-      filePath: filePath,
+    return await invoke<UploadedFile>('upload_seqta_file', {
+      fileName,
+      filePath,
+      uploadId,
+      acceptMimes,
     });
-    return response;
   } catch (error) {
     console.error('uploadSeqtaFile error:', error);
-    throw new Error(error instanceof Error ? error.message : 'Unknown upload error');
+    throw toError(error, 'Unknown upload error');
+  }
+}
+
+export async function cancelUpload(uploadId: string): Promise<boolean> {
+  return invoke<boolean>('cancel_upload', { id: uploadId });
+}
+
+export interface SubmissionFileResult {
+  path: string;
+  upload_id: string;
+  status: 'submitted' | 'not_verified' | 'uploaded' | 'failed' | 'cancelled';
+  file: UploadedFile | null;
+  error: string | null;
+}
+
+export interface SubmissionResult {
+  success: boolean;
+  files: SubmissionFileResult[];
+  submissions: any[];
+}
+
+export async function submitAssessment(
+  assessmentId: number,
+  metaclassId: number,
+  files: string[],
+  submitId?: string,
+): Promise<SubmissionResult> {
+  try {
+    return await invoke<SubmissionResult>('submit_assessment', { assessmentId, metaclassId, files, submitId });
+  } catch (error) {
+    console.error('submitAssessment error:', error);
+    throw toError(error, 'Unknown submission error');
   }
-}
\ No newline at end of file
+}
+
+export interface AggregatedFeedItem {
+  id: string;
+  feed_url: string;
+  feed_title: string;
+  title: string | null;
+  link: string | null;
+  description: string | null;
+  pub_date: string | null;
+  author: string | null;
+  read: boolean;
+  starred: boolean;
+  first_seen: string;
+}
+
+export interface FeedSource {
+  url: string;
+  title: string;
+  category: string | null;
+  last_fetched: string | null;
+  error: string | null;
+  unread: number;
+}
+
+export async function getAggregatedFeed(limit?: number, unreadOnly?: boolean): Promise<AggregatedFeedItem[]> {
+  return invoke<AggregatedFeedItem[]>('get_aggregated_feed', { limit, unreadOnly });
+}
+
+export async function refreshFeeds(): Promise<{ new_items: number; failed: { url: string; error: string }[] }> {
+  return invoke('refresh_feeds');
+}
+
+export async function getFeedSources(): Promise<FeedSource[]> {
+  return invoke<FeedSource[]>('get_feed_sources');
+}
+
+export async function setFeedItemRead(id: string, read: boolean): Promise<void> {
+  await invoke('set_feed_item_read', { id, read });
+}
+
+export async function setFeedItemStarred(id: string, starred: boolean): Promise<void> {
+  await invoke('set_feed_item_starred', { id, starred });
+}
+
+export interface FeedArticle {
+  url: string;
+  title: string | null;
+  byline: string | null;
+  site_name: string | null;
+  excerpt: string | null;
+  /** Sanitised HTML of the page's main content. */
+  content: string;
+  word_count: number;
+}
+
+export async function getFeedArticle(url: string): Promise<FeedArticle> {
+  return invoke<FeedArticle>('get_feed_article', { url });
+}